@@ -0,0 +1,248 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Index, Member, PathArguments, Type, TypePath};
+
+// A field of a bundle struct
+struct BundleField {
+    member: Member,
+    ty: Type,
+    kind: FieldKind,
+}
+
+enum FieldKind {
+    Component,
+    // Option<C> fields, the component is only inserted if present
+    Optional(Box<Type>),
+    // #[bundle] fields, flattened into the parent bundle
+    Bundle,
+}
+
+impl BundleField {
+    // the type of the component stored in the table, if this field is a single component
+    fn component_type(&self) -> Option<&Type> {
+        match &self.kind {
+            FieldKind::Component => Some(&self.ty),
+            FieldKind::Optional(inner) => Some(inner),
+            FieldKind::Bundle => None,
+        }
+    }
+}
+
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_bundle_impl(input) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn derive_bundle_impl(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => collect_fields(&data.fields)?,
+        Data::Enum(data) => return Err(syn::Error::new(data.enum_token.span(), "Bundle can only be derived for structs")),
+        Data::Union(data) => return Err(syn::Error::new(data.union_token.span(), "Bundle can only be derived for structs")),
+    };
+
+    check_duplicate_components(&fields)?;
+    let options = parse_options(&input)?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+    for field in fields.iter() {
+        where_clause.predicates.push(match field.component_type() {
+            Some(ty) => syn::parse_quote!(#ty: 'static + Sized),
+            None => { let ty = &field.ty; syn::parse_quote!(#ty: ::osiris_ecs::storage::DynamicBundle) },
+        });
+        if let Some(ty) = field.component_type() {
+            if options.eq {
+                where_clause.predicates.push(syn::parse_quote!(#ty: ::std::cmp::PartialEq));
+            }
+            if options.hash {
+                where_clause.predicates.push(syn::parse_quote!(#ty: ::std::hash::Hash));
+            }
+        }
+    }
+
+    // the metadata of a component field, with eq and hash filled in if the bundle opted in
+    let metadata_of = |ty: &Type| {
+        let eq = options.eq.then(|| quote! { .with_eq::<#ty>() });
+        let hash = options.hash.then(|| quote! { .with_hash::<#ty>() });
+        quote! { ::osiris_ecs::storage::TypeMetadata::of::<#ty>() #eq #hash }
+    };
+
+    let metadata_parts: Vec<_> = fields.iter().map(|BundleField { ty, kind, .. }| match kind {
+        FieldKind::Component => { let metadata = metadata_of(ty); quote! { &[#metadata] } },
+        FieldKind::Optional(inner) => { let metadata = metadata_of(inner); quote! { &[#metadata] } },
+        FieldKind::Bundle => quote! { <#ty as ::osiris_ecs::storage::DynamicBundle>::METADATA },
+    }).collect();
+    let required_parts = fields.iter().map(|BundleField { ty, kind, .. }| match kind {
+        FieldKind::Component => { let metadata = metadata_of(ty); quote! { &[#metadata] } },
+        FieldKind::Optional(_) => quote! { &[] },
+        FieldKind::Bundle => quote! { <#ty as ::osiris_ecs::storage::DynamicBundle>::REQUIRED },
+    });
+    // where each field's types start in declaration order, put and take map these to METADATA through the layout
+    let starts: Vec<_> = fields.iter().scan(quote! { 0 }, |start, BundleField { ty, kind, .. }| {
+        let len = match kind {
+            FieldKind::Bundle => quote! { <#ty as ::osiris_ecs::storage::DynamicBundle>::METADATA.len() },
+            _ => quote! { 1 },
+        };
+        let field_start = start.clone();
+        *start = quote! { #start + #len };
+        Some(field_start)
+    }).collect();
+
+    let instance_metadata = fields.iter().map(|BundleField { member, ty, kind }| match kind {
+        FieldKind::Component => { let metadata = metadata_of(ty); quote_spanned! { ty.span() => f(#metadata); } },
+        FieldKind::Optional(inner) => { let metadata = metadata_of(inner); quote_spanned! { ty.span() =>
+            if self.#member.is_some() { f(#metadata); }
+        } },
+        FieldKind::Bundle => quote_spanned! { ty.span() => <#ty as ::osiris_ecs::storage::DynamicBundle>::instance_metadata(&self.#member, &mut f); },
+    });
+    let puts = fields.iter().zip(&starts).map(|(BundleField { member, ty, kind }, start)| match kind {
+        FieldKind::Component => quote_spanned! { ty.span() => f((&raw mut this.#member).cast::<u8>(), layout.sorted_index(#start)); },
+        FieldKind::Optional(_) => quote_spanned! { ty.span() =>
+            if let Some(value) = unsafe { ::std::ptr::read(&this.#member) } {
+                // the table owns the value once it's been copied out
+                let mut value = ::std::mem::ManuallyDrop::new(value);
+                f((&raw mut *value).cast::<u8>(), layout.sorted_index(#start));
+            }
+        },
+        FieldKind::Bundle => quote_spanned! { ty.span() =>
+            unsafe { <#ty as ::osiris_ecs::storage::DynamicBundle>::put(::std::ptr::read(&this.#member), |ptr, idx| f(ptr, layout.sorted_index(#start + idx))) };
+        },
+    });
+    let takes = fields.iter().zip(&starts).map(|(BundleField { member, ty, kind }, start)| match kind {
+        FieldKind::Component => quote_spanned! { ty.span() =>
+            let is_present = f((&raw mut (*raw).#member).cast::<u8>(), layout.sorted_index(#start));
+            assert!(is_present, "Non-optional bundle fields are required");
+        },
+        FieldKind::Optional(inner) => quote_spanned! { ty.span() =>
+            let mut value = ::std::mem::MaybeUninit::<#inner>::uninit();
+            let is_present = f(value.as_mut_ptr().cast::<u8>(), layout.sorted_index(#start));
+            (&raw mut (*raw).#member).write(if is_present { Some(value.assume_init()) } else { None });
+        },
+        FieldKind::Bundle => quote_spanned! { ty.span() =>
+            (&raw mut (*raw).#member).write(<#ty as ::osiris_ecs::storage::DynamicBundle>::take(|ptr, idx| f(ptr, layout.sorted_index(#start + idx))));
+        },
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::osiris_ecs::storage::HasBundleLayout for #name #ty_generics #where_clause {
+            const LAYOUT: &'static ::osiris_ecs::storage::BundleLayout = &::osiris_ecs::storage::BundleLayout::new(&[#(#metadata_parts),*]);
+        }
+
+        unsafe impl #impl_generics ::osiris_ecs::storage::DynamicBundle for #name #ty_generics #where_clause {
+            const METADATA: &'static [::osiris_ecs::storage::TypeMetadata] = <Self as ::osiris_ecs::storage::HasBundleLayout>::LAYOUT.metadata();
+            const REQUIRED: &'static [::osiris_ecs::storage::TypeMetadata] = {
+                let required: &'static ::osiris_ecs::storage::BundleLayout = &<Self as ::osiris_ecs::storage::HasBundleLayout>::LAYOUT.retain(&[#(#required_parts),*]);
+                required.metadata()
+            };
+
+            fn instance_metadata(&self, mut f: impl FnMut(::osiris_ecs::storage::TypeMetadata)) {
+                #(#instance_metadata)*
+            }
+
+            unsafe fn put(self, mut f: impl FnMut(*mut u8, usize)) {
+                #[allow(unused_variables)]
+                let layout = <Self as ::osiris_ecs::storage::HasBundleLayout>::LAYOUT;
+                // fields are moved out by pointer, so the struct itself must not be dropped
+                #[allow(unused_mut)]
+                let mut this = ::std::mem::ManuallyDrop::new(self);
+                #(#puts)*
+            }
+
+            unsafe fn take(mut f: impl FnMut(*mut u8, usize) -> bool) -> Self {
+                #[allow(unused_variables)]
+                let layout = <Self as ::osiris_ecs::storage::HasBundleLayout>::LAYOUT;
+                let mut output = ::std::mem::MaybeUninit::<Self>::uninit();
+                let raw = output.as_mut_ptr();
+                unsafe {
+                    #(#takes)*
+                    output.assume_init()
+                }
+            }
+        }
+    })
+}
+
+// #[bundle(eq, hash)] on the struct fills in TypeMetadata::eq / hash for its own component fields, so tables made
+// for it can be compared and checksummed. Nested bundles opt in on their own
+#[derive(Default)]
+struct BundleOptions {
+    eq: bool,
+    hash: bool,
+}
+
+fn parse_options(input: &DeriveInput) -> syn::Result<BundleOptions> {
+    let mut options = BundleOptions::default();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("bundle")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("eq") {
+                options.eq = true;
+            } else if meta.path.is_ident("hash") {
+                options.hash = true;
+            } else {
+                return Err(meta.error("Expected `eq` or `hash`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn collect_fields(fields: &Fields) -> syn::Result<Vec<BundleField>> {
+    fields.iter().enumerate().map(|(idx, field)| {
+        let mut is_bundle = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("bundle")) {
+            attr.meta.require_path_only()?;
+            is_bundle = true;
+        }
+
+        let kind = match (is_bundle, option_inner_type(&field.ty)) {
+            (true, Some(_)) => return Err(syn::Error::new(field.ty.span(), "Optional nested bundles are not supported")),
+            (true, None) => FieldKind::Bundle,
+            (false, Some(inner)) => FieldKind::Optional(Box::new(inner.clone())),
+            (false, None) => FieldKind::Component,
+        };
+
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(idx)),
+        };
+
+        Ok(BundleField { member, ty: field.ty.clone(), kind })
+    }).collect()
+}
+
+// Option<T> is recognised by name, so a type alias of Option will be treated as a plain component
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(TypePath { qself: None, path }) = ty else { return None };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    match args.args.iter().collect::<Vec<_>>().as_slice() {
+        [GenericArgument::Type(inner)] => Some(inner),
+        _ => None,
+    }
+}
+
+// Catches the obvious duplicates early with a nice span, anything else (e.g. aliases or nested bundles) is
+// rejected when the bundle's METADATA is evaluated
+fn check_duplicate_components(fields: &[BundleField]) -> syn::Result<()> {
+    let components: Vec<_> = fields.iter()
+        .filter_map(|field| field.component_type().map(|ty| (field, quote!(#ty).to_string())))
+        .collect();
+
+    for (idx, (field, ty)) in components.iter().enumerate() {
+        if components[..idx].iter().any(|(_, other)| other == ty) {
+            return Err(syn::Error::new(field.ty.span(), format!("Component type `{}` appears more than once in this bundle", ty)));
+        }
+    }
+    Ok(())
+}
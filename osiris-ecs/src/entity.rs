@@ -0,0 +1,116 @@
+// A handle to one row of a world. The generation tells apart entities that reuse the same index after a despawn
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+// A component pointing at another entity, e.g. a turret's target, which join queries can follow
+pub trait Relation: 'static {
+    fn target(&self) -> Entity;
+}
+
+impl Relation for Entity {
+    fn target(&self) -> Entity {
+        *self
+    }
+}
+
+// The entity above this one in a hierarchy, targeted triggers bubble up through it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Parent(pub Entity);
+
+impl Relation for Parent {
+    fn target(&self) -> Entity {
+        self.0
+    }
+}
+
+// Marks the rows of disabled entities, queries skip tables with it unless they access it or include disabled rows
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Disabled;
+
+// Where an entity's row currently is
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EntityLocation {
+    pub table: usize,
+    pub row: usize,
+}
+
+// Hands out entities and maps the live ones to their rows
+#[derive(Default)]
+pub struct Entities {
+    slots: Vec<Slot>,
+    // indices of despawned entities, reused before growing slots
+    free: Vec<u32>,
+    live: usize,
+}
+
+struct Slot {
+    generation: u32,
+    // None once the entity was despawned
+    location: Option<EntityLocation>,
+}
+
+impl Entities {
+    pub fn alloc(&mut self, location: EntityLocation) -> Entity {
+        self.live += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.location = Some(location);
+            return Entity { index, generation: slot.generation };
+        }
+        let index = u32::try_from(self.slots.len()).expect("Too many entities");
+        self.slots.push(Slot { generation: 0, location: Some(location) });
+        Entity { index, generation: 0 }
+    }
+
+    // Returns where the entity was, or None if it was already dead
+    pub fn free(&mut self, entity: Entity) -> Option<EntityLocation> {
+        let location = self.location(entity)?;
+        let slot = &mut self.slots[entity.index as usize];
+        slot.location = None;
+        self.live -= 1;
+        // NB: an index whose generation would wrap is retired instead of reused
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free.push(entity.index);
+        }
+        Some(location)
+    }
+
+    pub fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        self.slots.get(entity.index as usize)
+            .filter(|slot| slot.generation == entity.generation)
+            .and_then(|slot| slot.location)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.location(entity).is_some()
+    }
+
+    // the entity must be alive
+    pub(crate) fn set_location(&mut self, entity: Entity, location: EntityLocation) {
+        debug_assert!(self.contains(entity));
+        self.slots[entity.index as usize].location = Some(location);
+    }
+
+    // the number of live entities
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
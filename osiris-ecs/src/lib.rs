@@ -1,6 +1,10 @@
-#![feature(const_type_id)]
-#![feature(alloc_layout_extra)]
 #![feature(const_trait_impl)]
-#![feature(ptr_as_uninit)]
+#![feature(const_cmp)]
+#![feature(const_type_name)]
 
+// lets derived code refer to ::osiris_ecs from inside this crate too
+extern crate self as osiris_ecs;
+
+pub mod entity;
 pub mod storage;
+pub mod world;
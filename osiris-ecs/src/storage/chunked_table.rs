@@ -0,0 +1,124 @@
+use crate::storage::raw_table::{ColumnAlignment, RawTable, RowInfo};
+use crate::storage::type_data::TypeMetadata;
+use std::any::TypeId;
+
+// A table made of fixed capacity RawTables, growing adds a chunk so existing rows never move
+pub struct ChunkedTable {
+    chunks: Vec<RawTable>,
+    chunk_size: usize,
+    chunk_capacity: usize,
+    alignment: ColumnAlignment,
+    // the columns of every chunk, with dangling pointers
+    rows: RowInfo,
+}
+
+impl ChunkedTable {
+    // chunk_size is the maximum number of bytes per chunk, each chunk holds at least one row
+    pub fn new(type_infos: impl IntoIterator<Item = TypeMetadata>, chunk_size: usize, alignment: ColumnAlignment) -> Self {
+        Self::from_template(RawTable::with_alignment(type_infos, alignment), chunk_size)
+    }
+
+    fn from_template(template: RawTable, chunk_size: usize) -> Self {
+        let row_size: usize = template.type_metadata().map(|TypeMetadata { layout, .. }| layout.pad_to_align().size()).sum();
+        let lanes = template.alignment().lanes;
+        // the estimate ignores padding between columns, so shrink (in whole lanes) until it actually fits
+        let mut chunk_capacity = (chunk_size / row_size.max(1) / lanes * lanes).max(lanes);
+        while chunk_capacity > lanes && template.layout_for_capacity(chunk_capacity).0.size() > chunk_size {
+            chunk_capacity -= lanes;
+        }
+
+        Self {
+            alignment: template.alignment(),
+            chunks: Vec::new(),
+            chunk_size,
+            chunk_capacity,
+            rows: template.row_info().clone(),
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn alignment(&self) -> ColumnAlignment {
+        self.alignment
+    }
+
+    pub fn chunk_capacity(&self) -> usize {
+        self.chunk_capacity
+    }
+
+    pub fn chunks(&self) -> &[RawTable] {
+        &self.chunks
+    }
+
+    // the chunk holding a row, and the row's index within it
+    pub fn locate(&self, idx: usize) -> (&RawTable, usize) {
+        (&self.chunks[idx / self.chunk_capacity], idx % self.chunk_capacity)
+    }
+
+    // Ensure this table can store at least capacity
+    pub fn reserve(&mut self, total_capacity: usize) {
+        while self.capacity() < total_capacity {
+            let mut chunk = RawTable::with_alignment_unchecked(self.rows.type_metadata(), self.alignment);
+            chunk.reserve(self.chunk_capacity);
+            self.chunks.push(chunk);
+        }
+    }
+
+    pub fn row_info(&self) -> &RowInfo {
+        &self.rows
+    }
+
+    pub fn column_search_dynamic(&self, idx: usize, type_id: TypeId) -> Option<(TypeMetadata, *mut u8)> {
+        let (chunk, chunk_idx) = self.locate(idx);
+        chunk.column_search_dynamic(chunk_idx, type_id)
+    }
+
+    pub fn column_at(&self, idx: usize, position: usize) -> (TypeMetadata, *mut u8) {
+        let (chunk, chunk_idx) = self.locate(idx);
+        chunk.column_at(chunk_idx, position)
+    }
+
+    pub fn column_iter(&self, idx: usize) -> impl Iterator<Item=(TypeMetadata, *mut u8)> {
+        let (chunk, chunk_idx) = self.locate(idx);
+        chunk.column_iter(chunk_idx)
+    }
+
+    pub unsafe fn drop_column(&self, idx: usize) {
+        let (chunk, chunk_idx) = self.locate(idx);
+        unsafe { chunk.drop_column(chunk_idx) }
+    }
+
+    pub unsafe fn swap_columns(&self, idx_a: usize, idx_b: usize) {
+        if idx_a != idx_b {
+            // every chunk is built from the same row info, so the columns line up
+            for ((TypeMetadata { layout, .. }, ptr_a), (_, ptr_b)) in std::iter::zip(self.column_iter(idx_a), self.column_iter(idx_b)) {
+                unsafe { std::ptr::swap_nonoverlapping(ptr_a, ptr_b, layout.size()) };
+            }
+        }
+    }
+
+    pub unsafe fn move_columns(&self, src_start: usize, src_len: usize, dst_start: usize) {
+        // rows are moved one at a time as ranges may cross chunks, in an order that is safe for overlapping ranges
+        let move_column = |offset: usize| {
+            for ((TypeMetadata { layout, .. }, src_ptr), (_, dst_ptr)) in std::iter::zip(self.column_iter(src_start + offset), self.column_iter(dst_start + offset)) {
+                unsafe { std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, layout.size()) };
+            }
+        };
+
+        if dst_start < src_start {
+            (0..src_len).for_each(move_column);
+        } else if dst_start > src_start {
+            (0..src_len).rev().for_each(move_column);
+        }
+    }
+
+    pub unsafe fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.chunks.len() * self.chunk_capacity
+    }
+}
@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::entity::Entity;
+use crate::storage::{TypeAccess, TypeMetadata};
+
+// Why a bundle can't be used with a table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BundleError {
+    // the table has a column the bundle has no place for
+    MissingFromBundle(TypeMetadata),
+    // the bundle has a type the table has no column for
+    MissingFromTable(TypeMetadata),
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::MissingFromBundle(metadata) => write!(f, "Table column `{}` is missing from the bundle", metadata.name),
+            BundleError::MissingFromTable(metadata) => write!(f, "Bundle type `{}` is missing from the table", metadata.name),
+        }
+    }
+}
+
+impl Error for BundleError {}
+
+// Why two tables can't be compared or a table can't be hashed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompareError {
+    // the tables don't have the same columns
+    Incompatible(BundleError),
+    // a column's metadata has no eq function
+    MissingEq(TypeMetadata),
+    // a column's metadata has no hash function
+    MissingHash(TypeMetadata),
+}
+
+impl Display for CompareError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompareError::Incompatible(err) => write!(f, "Tables have different columns: {err}"),
+            CompareError::MissingEq(metadata) => write!(f, "Table column `{}` can't be compared, its metadata has no eq", metadata.name),
+            CompareError::MissingHash(metadata) => write!(f, "Table column `{}` can't be hashed, its metadata has no hash", metadata.name),
+        }
+    }
+}
+
+impl Error for CompareError {}
+
+// Why a query can't borrow its columns
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessError {
+    // the query asks for a type more than once, and at least once mutably
+    Aliased(TypeAccess),
+    // a live query already holds a borrow that conflicts with this one
+    AlreadyBorrowed(TypeAccess),
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessError::Aliased(access) => write!(f, "`{}` is accessed more than once in the same query, and mutably at least once", access.name),
+            AccessError::AlreadyBorrowed(access) if access.is_mutable => write!(f, "Can't borrow `{}` mutably, it is already borrowed", access.name),
+            AccessError::AlreadyBorrowed(access) => write!(f, "Can't borrow `{}`, it is already borrowed mutably", access.name),
+        }
+    }
+}
+
+impl Error for AccessError {}
+
+// Why a query can't fetch an entity's row
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QueryEntityError {
+    // the entity was despawned, or never existed
+    NoSuchEntity(Entity),
+    // the entity's table doesn't have every column the query accesses
+    QueryDoesNotMatch(Entity),
+    // the entity was asked for more than once by get_many_mut
+    AliasedMutability(Entity),
+}
+
+impl Display for QueryEntityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryEntityError::NoSuchEntity(entity) => write!(f, "Entity {entity:?} does not exist"),
+            QueryEntityError::QueryDoesNotMatch(entity) => write!(f, "Entity {entity:?} does not match the query"),
+            QueryEntityError::AliasedMutability(entity) => write!(f, "Entity {entity:?} was requested mutably more than once"),
+        }
+    }
+}
+
+impl Error for QueryEntityError {}
+
+// Why a query doesn't have exactly one row
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuerySingleError {
+    NoEntities,
+    MultipleEntities,
+}
+
+impl Display for QuerySingleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuerySingleError::NoEntities => write!(f, "The query has no rows, expected exactly one"),
+            QuerySingleError::MultipleEntities => write!(f, "The query has several rows, expected exactly one"),
+        }
+    }
+}
+
+impl Error for QuerySingleError {}
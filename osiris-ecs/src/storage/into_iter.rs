@@ -0,0 +1,58 @@
+use crate::storage::type_data::DynamicBundle;
+use crate::storage::Table;
+use std::marker::PhantomData;
+
+// Moves every row out of a table as a B, in order. Rows that aren't yielded are dropped along with the iterator
+pub struct IntoIter<B: DynamicBundle> {
+    table: Table,
+    // the rows still owned by the iterator
+    start: usize,
+    end: usize,
+    // where B's types are in the table
+    positions: Vec<Option<usize>>,
+    _marker: PhantomData<fn() -> B>,
+}
+
+impl<B: DynamicBundle> IntoIter<B> {
+    // table must be compatible with B
+    pub(super) unsafe fn new_unchecked(mut table: Table) -> Self {
+        // the table forgets its rows, they are dropped by the iterator instead
+        let end = std::mem::replace(&mut table.len, 0);
+        let positions = table.bundle_positions::<B>();
+        Self { table, start: 0, end, positions, _marker: PhantomData }
+    }
+}
+
+impl<B: DynamicBundle> Iterator for IntoIter<B> {
+    type Item = B;
+
+    fn next(&mut self) -> Option<B> {
+        if self.start == self.end {
+            return None;
+        }
+        self.start += 1;
+        Some(unsafe { self.table.take_column_at(self.start - 1, &self.positions) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.end - self.start, Some(self.end - self.start))
+    }
+}
+
+impl<B: DynamicBundle> DoubleEndedIterator for IntoIter<B> {
+    fn next_back(&mut self) -> Option<B> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { self.table.take_column_at(self.end, &self.positions) })
+    }
+}
+
+impl<B: DynamicBundle> ExactSizeIterator for IntoIter<B> {}
+
+impl<B: DynamicBundle> Drop for IntoIter<B> {
+    fn drop(&mut self) {
+        unsafe { self.table.buf.drop_columns(self.start, self.end) }
+    }
+}
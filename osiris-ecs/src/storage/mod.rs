@@ -1,37 +1,62 @@
-use crate::storage::raw_table::RawTable;
-use crate::storage::type_data::{DynamicBundle, TypeMetadata};
-
+use std::any::TypeId;
+use std::hash::Hasher;
+use std::mem::ManuallyDrop;
+use crate::storage::raw_table::drop_each;
+use crate::storage::table_storage::TableStorage;
+
+pub use osiris_ecs_macros::Bundle;
+pub use error::{AccessError, BundleError, CompareError, QueryEntityError, QuerySingleError};
+pub use raw_table::ColumnAlignment;
+pub use table_storage::{StorageMode, StorageOptions};
+pub use type_data::{BundleLayout, BundleRefs, Column, ColumnBundle, DynamicBundle, HasBundleLayout, Nested, TypeMetadata};
+pub use into_iter::IntoIter;
+pub use query::{Accessible, Accessor, Borrows, Combinations, DynamicQuery, DynamicRow, JoinQuery, Query, QueryState, ReadOnlyAccessible, TypeAccess};
+pub use typed_table::TypedTable;
+
+mod error;
 mod type_data;
 mod raw_table;
+mod chunked_table;
+mod table_storage;
+mod typed_table;
+mod into_iter;
 mod test;
 mod query;
 
 // We can't quite type mark this because we need to be able to construct a homogenous list of Tables :(
-struct Table {
-    buf: RawTable,
+// TypedTable wraps one for when the bundle type is known
+pub struct Table {
+    buf: TableStorage,
     len: usize,
 }
 
 impl Table {
     // -- INSTANTIATION -- //
     pub fn new(types: impl IntoIterator<Item = TypeMetadata>) -> Self {
-        Self {
-            buf: RawTable::new(types),
-            len: 0,
-        }
+        Self::with_storage(types, StorageOptions::default())
     }
 
     pub fn new_for_bundle<B: DynamicBundle>() -> Self {
+        Self::with_storage_for_bundle::<B>(StorageOptions::default())
+    }
+
+    pub fn with_storage(types: impl IntoIterator<Item = TypeMetadata>, options: impl Into<StorageOptions>) -> Self {
         Self {
-            buf: RawTable::new_unchecked(B::type_metadata()),
+            buf: TableStorage::new(types, options.into()),
             len: 0,
         }
     }
 
-    pub fn from_iter<I: IntoIterator<Item: DynamicBundle, IntoIter: ExactSizeIterator<Item: DynamicBundle>>>(iter: I) -> Self {
-        let mut init = Self::new_for_bundle::<I::Item>();
-        init.extend(iter);
-        init
+    pub fn with_storage_for_bundle<B: DynamicBundle>(options: impl Into<StorageOptions>) -> Self {
+        // B::METADATA is checked for duplicates and sorted at compile time
+        Self::with_storage(B::METADATA.iter().copied(), options)
+    }
+
+    // only the types actually present in data, for bundles with optional types
+    pub fn new_for_instance<B: DynamicBundle>(data: &B) -> Self {
+        let mut types = Vec::with_capacity(B::METADATA.len());
+        data.instance_metadata(|metadata| types.push(metadata));
+        Self::new(types)
     }
 
     pub fn from_fn<B: DynamicBundle>(n: usize, f: impl FnMut(usize) -> B) -> Self {
@@ -43,32 +68,174 @@ impl Table {
     // --- META OPERATIONS --- //
     pub fn len(&self) -> usize { self.len }
     pub fn capacity(&self) -> usize { self.buf.capacity() }
-    pub fn empty(&self) -> bool { self.len == 0 }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+    pub fn storage_options(&self) -> StorageOptions { self.buf.options() }
+    pub fn type_metadata(&self) -> impl Iterator<Item = TypeMetadata> { self.buf.type_metadata() }
 
     pub fn reserve(&mut self, capacity: usize) {
         self.buf.reserve(capacity);
     }
 
     pub fn clear(&mut self) {
-        for idx in 0..std::mem::replace(&mut self.len, 0) {
-            unsafe { self.buf.drop_column(idx) }
-        }
+        // the rows are forgotten before dropping, so a panicking drop can't leave any behind to drop twice
+        let len = std::mem::replace(&mut self.len, 0);
+        unsafe { self.buf.drop_columns(0, len) }
         unsafe { self.buf.clear(); }
     }
 
-    fn is_bundle_compatible<B: DynamicBundle>(&self) -> bool {
-        std::iter::zip(self.buf.type_metadata(), B::type_metadata()).all(|(a, b )| a == b)
+    // B can be taken from this table, i.e. B has a place for every column and the table has every type B requires
+    pub fn check_bundle<B: DynamicBundle>(&self) -> Result<(), BundleError> {
+        self.check_bundle_subset::<B>()?;
+        match self.buf.type_metadata().find(|metadata| !B::METADATA.contains(metadata)) {
+            Some(metadata) => Err(BundleError::MissingFromBundle(metadata)),
+            None => Ok(()),
+        }
+    }
+
+    // data can be put into this table, i.e. it has exactly the table's types
+    pub fn check_instance<B: DynamicBundle>(&self, data: &B) -> Result<(), BundleError> {
+        if B::REQUIRED.len() == B::METADATA.len() {
+            return self.check_bundle::<B>();
+        }
+
+        let mut present = Vec::with_capacity(B::METADATA.len());
+        data.instance_metadata(|metadata| present.push(metadata));
+        if let Some(&metadata) = present.iter().find(|metadata| self.buf.row_info().search_dynamic(metadata.id).is_none()) {
+            return Err(BundleError::MissingFromTable(metadata));
+        }
+        match self.buf.type_metadata().find(|metadata| !present.contains(metadata)) {
+            Some(metadata) => Err(BundleError::MissingFromBundle(metadata)),
+            None => Ok(()),
+        }
+    }
+
+    // B can be projected out of this table, i.e. the table has every type B requires
+    pub fn check_bundle_subset<B: DynamicBundle>(&self) -> Result<(), BundleError> {
+        match B::REQUIRED.iter().find(|metadata| self.buf.row_info().search_dynamic(metadata.id).is_none()) {
+            Some(&metadata) => Err(BundleError::MissingFromTable(metadata)),
+            None => Ok(()),
+        }
+    }
+
+    // other has exactly this table's types, so rows can be moved between them as they are
+    pub fn check_table(&self, other: &Table) -> Result<(), BundleError> {
+        if let Some(metadata) = other.buf.type_metadata().find(|metadata| self.buf.row_info().search_dynamic(metadata.id).is_none()) {
+            return Err(BundleError::MissingFromTable(metadata));
+        }
+        match self.buf.type_metadata().find(|metadata| other.buf.row_info().search_dynamic(metadata.id).is_none()) {
+            Some(metadata) => Err(BundleError::MissingFromBundle(metadata)),
+            None => Ok(()),
+        }
+    }
+
+    // --- COLUMN ACCESS --- //
+
+    // The column of T as one slice per block of storage, i.e. one slice unless the table is chunked
+    pub fn column_slices<T: 'static>(&self) -> impl Iterator<Item = &[T]> {
+        self.column_chunk_ptrs::<T>().map(|(ptr, len)| unsafe { std::slice::from_raw_parts(ptr, len) })
+    }
+
+    pub fn column_slices_mut<T: 'static>(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.column_chunk_ptrs::<T>().map(|(ptr, len)| unsafe { std::slice::from_raw_parts_mut(ptr, len) })
+    }
+
+    // The column of T in batches of N, with the rows that don't fill a batch as a remainder. Only the last block
+    // of storage has a remainder if the table's capacity is padded to a multiple of N lanes
+    pub fn column_chunks<T: 'static, const N: usize>(&self) -> impl Iterator<Item = (&[[T; N]], &[T])> {
+        self.column_slices::<T>().map(|slice| slice.as_chunks::<N>())
+    }
+
+    pub fn column_chunks_mut<T: 'static, const N: usize>(&mut self) -> impl Iterator<Item = (&mut [[T; N]], &mut [T])> {
+        self.column_slices_mut::<T>().map(|slice| slice.as_chunks_mut::<N>())
+    }
+
+    fn column_chunk_ptrs<T: 'static>(&self) -> impl Iterator<Item = (*mut T, usize)> {
+        if self.buf.row_info().search::<T>().is_none() {
+            panic!("{}", BundleError::MissingFromTable(TypeMetadata::of::<T>()));
+        }
+        self.column_segments(TypeId::of::<T>(), 0, self.len).map(|(ptr, len)| (ptr.cast::<T>(), len))
+    }
+
+    // Per block of storage, the first row of each column with its stride, and how many of its rows are in use.
+    // Columns are given by their position in the table's row info
+    fn column_blocks_at(&self, positions: impl AsRef<[usize]>) -> impl Iterator<Item = (Vec<(*mut u8, usize)>, usize)> {
+        let mut remaining = self.len;
+        self.buf.chunks().map_while(move |(chunk, capacity)| {
+            if remaining == 0 {
+                return None;
+            }
+            let len = remaining.min(capacity);
+            remaining -= len;
+            let columns = positions.as_ref().iter().map(|&position| {
+                let (TypeMetadata { layout, .. }, ptr) = chunk.row_info().get(position);
+                (ptr.as_ptr(), layout.pad_to_align().size())
+            }).collect();
+            Some((columns, len))
+        })
+    }
+
+    // Rows start..end of a column, split into runs that are contiguous in memory
+    fn column_segments(&self, type_id: TypeId, start: usize, end: usize) -> impl Iterator<Item = (*mut u8, usize)> {
+        let mut chunk_start = 0;
+        self.buf.chunks().filter_map(move |(chunk, capacity)| {
+            let (first, last) = (start.max(chunk_start), end.min(chunk_start + capacity));
+            let offset = first - chunk_start;
+            chunk_start += capacity;
+            if first >= last {
+                return None;
+            }
+            let (TypeMetadata { layout, .. }, ptr) = chunk.row_info().search_dynamic(type_id)?;
+            Some((unsafe { ptr.add(layout.pad_to_align().size() * offset).as_ptr() }, last - first))
+        })
+    }
+
+    // Adds a column of T, initialised with f(idx) for every existing row. Only per column tables can add
+    // columns that take up space, any table can add a zero sized type
+    pub fn add_column<T: 'static>(&mut self, f: impl FnMut(usize) -> T) {
+        assert!(self.buf.row_info().search::<T>().is_none(), "Table already has a column of `{}`", std::any::type_name::<T>());
+        assert!(self.buf.can_change_columns(TypeMetadata::of::<T>()), "Columns of `{}` can't be added to this table", std::any::type_name::<T>());
+        // collect first so a panic in f can't leave the column half initialised
+        let values: Vec<T> = (0..self.len).map(f).collect();
+
+        self.buf.add_column(TypeMetadata::of::<T>());
+        for (idx, value) in values.into_iter().enumerate() {
+            let (_, dst_ptr) = self.buf.column_search_dynamic(idx, TypeId::of::<T>()).expect("Column was just added");
+            unsafe { dst_ptr.cast::<T>().write(value) };
+        }
+    }
+
+    // Removes the column of T, handing back its values in row order
+    pub fn remove_column<T: 'static>(&mut self) -> Vec<T> {
+        assert!(self.buf.can_change_columns(TypeMetadata::of::<T>()), "Columns of `{}` can't be removed from this table", std::any::type_name::<T>());
+        let mut values = Vec::<T>::with_capacity(self.len);
+        for slice in self.column_slices::<T>() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(slice.as_ptr(), values.as_mut_ptr().add(values.len()), slice.len());
+                values.set_len(values.len() + slice.len());
+            }
+        }
+        self.buf.remove_column(TypeId::of::<T>());
+        values
     }
 
     // --- SINGLE OPERATIONS --- //
 
+    // Where each type in B::METADATA is in the row info, so rows of B can be put or taken without searching
+    fn bundle_positions<B: DynamicBundle>(&self) -> Vec<Option<usize>> {
+        self.buf.row_info().positions(B::METADATA)
+    }
+
     // unchecked bundle operation primitive
-    unsafe fn put_column_unchecked(&self, idx: usize, data: impl DynamicBundle) {
+    unsafe fn put_column_unchecked<B: DynamicBundle>(&self, idx: usize, data: B) {
+        unsafe { self.put_column_at(idx, data, &self.bundle_positions::<B>()) }
+    }
+
+    // unchecked bundle operation primitive, positions are from bundle_positions::<B>
+    unsafe fn put_column_at<B: DynamicBundle>(&self, idx: usize, data: B, positions: &[Option<usize>]) {
         unsafe {
-            let mut column = self.buf.column_iter(idx);
-            data.put(| src_ptr, src_id | {
-                let (TypeMetadata { id: dst_id, layout, ..} , dst_ptr) = column.next().expect("Compatible Bundles must have same number of type ids as table");
-                assert_eq!(src_id, dst_id, "Compatible bundles must have types identically ordered");
+            data.put(| src_ptr, type_idx | {
+                let position = positions[type_idx].expect("Compatible bundles must only contain types in the table");
+                let (TypeMetadata { layout, ..} , dst_ptr) = self.buf.column_at(idx, position);
                 std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, layout.size());
             });
         }
@@ -76,34 +243,52 @@ impl Table {
 
     // unchecked bundle operation primitive
     unsafe fn take_column_unchecked<B: DynamicBundle>(&self, idx: usize) -> B {
+        unsafe { self.take_column_at(idx, &self.bundle_positions::<B>()) }
+    }
+
+    // unchecked bundle operation primitive, positions are from bundle_positions::<B>
+    unsafe fn take_column_at<B: DynamicBundle>(&self, idx: usize, positions: &[Option<usize>]) -> B {
         unsafe {
-            let mut column = self.buf.column_iter(idx);
-            B::take(| dst_ptr, dst_id | {
-                let (TypeMetadata { id: src_id, layout, ..} , src_ptr) = column.next().expect("Bundle must have same type ids as table");
-                debug_assert_eq!(src_id, dst_id, "Compatible bundles must have types identically ordered");
-                std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, layout.size());
+            B::take(| dst_ptr, type_idx | {
+                match positions[type_idx] {
+                    Some(position) => {
+                        let (TypeMetadata { layout, .. }, src_ptr) = self.buf.column_at(idx, position);
+                        std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, layout.size());
+                        true
+                    }
+                    None => false
+                }
             })
         }
     }
 
-    // unchecked bundle operation primitive
-    unsafe fn put_column_from_iter_unchecked(&self, idx: usize, columns: impl IntoIterator<Item: DynamicBundle, IntoIter: ExactSizeIterator<Item: DynamicBundle>>) -> usize {
-        let mut count = 0;
-        for (data, mut column) in std::iter::zip(columns.into_iter(), self.buf.column_iter_range(idx, self.capacity() - idx)) {
-            unsafe {
-                data.put(|src_ptr, src_id| {
-                    let (TypeMetadata { id: dst_id, layout, .. }, dst_ptr) = column.next().expect("Compatible Bundles must have same number of type ids as table");
-                    debug_assert_eq!(src_id, dst_id, "Compatible bundles must have types identically ordered");
-                    std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, layout.size());
-                });
-            }
-            count += 1;
+    // unchecked bundle operation primitive, drops every column B doesn't take
+    unsafe fn take_partial_column_unchecked<B: DynamicBundle>(&self, idx: usize) -> B {
+        let output = unsafe { self.take_column_unchecked::<B>(idx) };
+        let mut taken = Vec::with_capacity(B::METADATA.len());
+        output.instance_metadata(|metadata| taken.push(metadata.id));
+        // if a drop panics the output is dropped while unwinding, along with the rest of the row
+        unsafe { drop_each(self.buf.column_iter(idx).filter(|(metadata, _)| !taken.contains(&metadata.id))) }
+        output
+    }
+
+    // unchecked bundle operation primitive, appends rows until the iterator or the capacity runs out. len is
+    // bumped after every row so the rows written so far are still owned by the table if the iterator panics
+    unsafe fn put_column_from_iter_unchecked<I: IntoIterator<Item: DynamicBundle>>(&mut self, columns: I) {
+        let positions = self.bundle_positions::<I::Item>();
+        unsafe { self.put_column_from_iter_at(columns, &positions) }
+    }
+
+    // Like put_column_from_iter_unchecked, positions are from bundle_positions::<I::Item>
+    unsafe fn put_column_from_iter_at<I: IntoIterator<Item: DynamicBundle>>(&mut self, columns: I, positions: &[Option<usize>]) {
+        for data in columns.into_iter().take(self.capacity() - self.len) {
+            unsafe { self.put_column_at(self.len, data, positions); }
+            self.len += 1;
         }
-        count
     }
 
     pub fn push<B: DynamicBundle>(&mut self, data: B) {
-        assert!(self.is_bundle_compatible::<B>());
+        self.check_instance(&data).unwrap_or_else(|err| panic!("{err}"));
         self.reserve(self.len + 1);
 
         unsafe { self.put_column_unchecked(self.len, data); }
@@ -113,7 +298,8 @@ impl Table {
 
     pub fn insert_at<B: DynamicBundle>(&mut self, idx: usize, data: B) -> B {
         assert!(idx < self.len);
-        assert!(self.is_bundle_compatible::<B>());
+        self.check_bundle::<B>().unwrap_or_else(|err| panic!("{err}"));
+        self.check_instance(&data).unwrap_or_else(|err| panic!("{err}"));
         unsafe {
             let output = self.take_column_unchecked(idx);
             self.put_column_unchecked(idx, data);
@@ -124,7 +310,7 @@ impl Table {
     // We let the outer scope drop this bundle
     pub fn swap_pop<B: DynamicBundle>(&mut self, idx: usize) -> B {
         assert!(idx < self.len);
-        assert!(self.is_bundle_compatible::<B>());
+        self.check_bundle::<B>().unwrap_or_else(|err| panic!("{err}"));
         // swap this and last item
         unsafe { self.buf.swap_columns(idx, self.len - 1) };
         // drop last item
@@ -145,32 +331,227 @@ impl Table {
 
     pub fn pop<B : DynamicBundle>(&mut self) -> B {
         assert!(self.len > 0);
-        assert!(self.is_bundle_compatible::<B>());
+        self.check_bundle::<B>().unwrap_or_else(|err| panic!("{err}"));
         self.len -= 1;
         unsafe { self.take_column_unchecked(self.len) }
     }
 
-    // --- BATCH OPERATIONS --- //
-    pub fn extend<I: IntoIterator<Item: DynamicBundle, IntoIter: ExactSizeIterator<Item: DynamicBundle>>>(&mut self, iter: I) {
-        assert!(self.is_bundle_compatible::<I::Item>(), "Incompatible bundles used!");
-        let mut iter = iter.into_iter();
-        let add_size = match iter.size_hint() {
-            (_, Some(upper)) => upper,
-            (lower, None) => lower,
-        };
+    // Like swap_pop, but B may be any subset of the table's types, the rest of the row is dropped
+    pub fn swap_pop_partial<B: DynamicBundle>(&mut self, idx: usize) -> B {
+        assert!(idx < self.len);
+        self.check_bundle_subset::<B>().unwrap_or_else(|err| panic!("{err}"));
+        unsafe { self.buf.swap_columns(idx, self.len - 1) };
+        self.pop_partial()
+    }
+
+    // Like pop, but B may be any subset of the table's types, the rest of the row is dropped
+    pub fn pop_partial<B: DynamicBundle>(&mut self) -> B {
+        assert!(self.len > 0);
+        self.check_bundle_subset::<B>().unwrap_or_else(|err| panic!("{err}"));
+        self.len -= 1;
+        unsafe { self.take_partial_column_unchecked(self.len) }
+    }
+
+    // Clones any subset of the table's types out of a row
+    pub fn get_cloned<B: DynamicBundle + Clone>(&self, idx: usize) -> B {
+        assert!(idx < self.len);
+        self.check_bundle_subset::<B>().unwrap_or_else(|err| panic!("{err}"));
+        // NB: This is a bitwise copy of the row, so it must never be dropped
+        let shallow = ManuallyDrop::new(unsafe { self.take_column_unchecked::<B>(idx) });
+        B::clone(&shallow)
+    }
+
+    // Moves every row out as a B, in order. Not IntoIterator as the caller picks B
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<B: DynamicBundle>(self) -> IntoIter<B> {
+        self.check_bundle::<B>().unwrap_or_else(|err| panic!("{err}"));
+        unsafe { IntoIter::new_unchecked(self) }
+    }
 
-        // attempt to pre-reserve the space
-        self.reserve(self.len + add_size);
+    // Moves a row into dst, which may have different columns. Columns dst lacks are dropped and extra fills in
+    // the columns this table lacks (or replaces the shared ones). Returns the index of the row in dst
+    pub fn move_row_to(&mut self, idx: usize, dst: &mut Table, extra: impl DynamicBundle) -> usize {
+        assert!(idx < self.len);
+        let mut extra_types = Vec::new();
+        extra.instance_metadata(|metadata| extra_types.push(metadata));
+        if let Some(&metadata) = extra_types.iter().find(|metadata| dst.buf.row_info().search_dynamic(metadata.id).is_none()) {
+            panic!("{}", BundleError::MissingFromTable(metadata));
+        }
+        if let Some(metadata) = dst.buf.type_metadata().find(|metadata| !extra_types.contains(metadata) && self.buf.row_info().search_dynamic(metadata.id).is_none()) {
+            panic!("{}", BundleError::MissingFromBundle(metadata));
+        }
+
+        dst.reserve(dst.len + 1);
+        let dst_idx = dst.len;
+        let mut leftover = Vec::new();
+        unsafe {
+            for (metadata@TypeMetadata { layout, .. }, src_ptr) in self.buf.column_iter(idx) {
+                match dst.buf.column_search_dynamic(dst_idx, metadata.id) {
+                    Some((_, dst_ptr)) if !extra_types.contains(&metadata) => std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, layout.size()),
+                    _ => leftover.push(metadata.id),
+                }
+            }
+            dst.put_column_unchecked(dst_idx, extra);
+        }
+        dst.len += 1;
 
+        // the row is swapped to the end and forgotten before the leftovers are dropped, so both tables are
+        // consistent if one of those drops panics
+        self.len -= 1;
         unsafe {
-            self.len += self.put_column_from_iter_unchecked(self.len, iter.by_ref().take(add_size));
+            self.buf.swap_columns(idx, self.len);
+            drop_each(self.buf.column_iter(self.len).filter(|(metadata, _)| leftover.contains(&metadata.id)));
+        }
+        dst_idx
+    }
+
+    // --- BATCH OPERATIONS --- //
+    pub fn extend<I: IntoIterator<Item: DynamicBundle>>(&mut self, iter: I) {
+        // bundles with optional types have to be checked one at a time
+        if I::Item::REQUIRED.len() != I::Item::METADATA.len() {
+            for item in iter {
+                self.push(item);
+            }
+            return;
         }
 
+        self.check_bundle::<I::Item>().unwrap_or_else(|err| panic!("Incompatible bundles used! {err}"));
+        let mut iter = iter.into_iter();
+
+        // attempt to pre-reserve the space, anything past the lower bound is pushed one at a time
+        self.reserve(self.len + iter.size_hint().0);
+
+        unsafe { self.put_column_from_iter_unchecked(iter.by_ref()) }
+
         for remaining_item in iter {
             self.push(remaining_item);
         }
     }
 
+    // Walks count rows of a column shared by two tables, calling f for each run that is contiguous in both until
+    // it returns false. Returns whether every run was visited
+    fn column_runs(a: &Table, a_start: usize, b: &Table, b_start: usize, count: usize, TypeMetadata { id, layout, .. }: TypeMetadata, mut f: impl FnMut(*mut u8, *mut u8, usize) -> bool) -> bool {
+        let size = layout.pad_to_align().size();
+        let mut a_segments = a.column_segments(id, a_start, a_start + count);
+        let mut b_segments = b.column_segments(id, b_start, b_start + count);
+        let (mut a_run, mut b_run) = (a_segments.next(), b_segments.next());
+        while let (Some((a_ptr, a_len)), Some((b_ptr, b_len))) = (a_run, b_run) {
+            let len = a_len.min(b_len);
+            if !f(a_ptr, b_ptr, len) {
+                return false;
+            }
+            a_run = if len == a_len { a_segments.next() } else { Some((unsafe { a_ptr.add(size * len) }, a_len - len)) };
+            b_run = if len == b_len { b_segments.next() } else { Some((unsafe { b_ptr.add(size * len) }, b_len - len)) };
+        }
+        true
+    }
+
+    // Copies count rows between tables with the same columns, one memcpy per run that is contiguous in both
+    unsafe fn copy_rows(src: &Table, src_start: usize, dst: &Table, dst_start: usize, count: usize) {
+        // zero sized types have nothing to copy
+        for &(metadata, _) in src.buf.row_info().iter() {
+            Self::column_runs(src, src_start, dst, dst_start, count, metadata, |src_ptr, dst_ptr, len| {
+                unsafe { std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, metadata.layout.size() * len) };
+                true
+            });
+        }
+    }
+
+    // Moves every row of other to the end of this table, leaving other empty
+    pub fn append(&mut self, other: &mut Table) {
+        self.check_table(other).unwrap_or_else(|err| panic!("Incompatible tables used! {err}"));
+        self.reserve(self.len + other.len);
+        unsafe { Self::copy_rows(other, 0, self, self.len, other.len) }
+        self.len += std::mem::replace(&mut other.len, 0);
+    }
+
+    // Moves the rows from at onwards into a new table with the same columns and storage options
+    pub fn split_off(&mut self, at: usize) -> Table {
+        assert!(at <= self.len);
+        let mut output = Table::with_storage(self.buf.type_metadata(), self.storage_options());
+        output.reserve(self.len - at);
+        unsafe { Self::copy_rows(self, at, &output, 0, self.len - at) }
+        output.len = std::mem::replace(&mut self.len, at) - at;
+        output
+    }
+
+    // Appends every row of others, e.g. to join tables built separately
+    pub fn merge(mut self, others: impl IntoIterator<Item = Table>) -> Table {
+        let mut others: Vec<Table> = others.into_iter().collect();
+        for other in others.iter() {
+            self.check_table(other).unwrap_or_else(|err| panic!("Incompatible tables used! {err}"));
+        }
+        self.reserve(self.len + others.iter().map(Table::len).sum::<usize>());
+        for other in others.iter_mut() {
+            self.append(other);
+        }
+        self
+    }
+
+    // --- COMPARISON --- //
+
+    // Row by row equality, every column needs an eq in its metadata. Zero sized columns hold no data so they are
+    // always equal
+    pub fn try_eq(&self, other: &Table) -> Result<bool, CompareError> {
+        self.check_table(other).map_err(CompareError::Incompatible)?;
+        let mut columns = Vec::with_capacity(self.buf.row_info().len());
+        for &(metadata, _) in self.buf.row_info().iter() {
+            columns.push((metadata, metadata.eq.ok_or(CompareError::MissingEq(metadata))?));
+        }
+        if self.len != other.len {
+            return Ok(false);
+        }
+
+        Ok(columns.into_iter().all(|(metadata, eq)| {
+            let size = metadata.layout.pad_to_align().size();
+            Self::column_runs(self, 0, other, 0, self.len, metadata, |a_ptr, b_ptr, len| {
+                (0..len).all(|idx| unsafe { eq(a_ptr.add(size * idx), b_ptr.add(size * idx)) })
+            })
+        }))
+    }
+
+    // Hashes every row, consistent with try_eq. Every column needs a hash in its metadata
+    pub fn try_hash<H: Hasher>(&self, state: &mut H) -> Result<(), CompareError> {
+        let mut columns = Vec::with_capacity(self.buf.row_info().len());
+        for &(metadata, _) in self.buf.row_info().iter() {
+            columns.push((metadata, metadata.hash.ok_or(CompareError::MissingHash(metadata))?));
+        }
+
+        state.write_u64(self.len as u64);
+        // columns are sorted by name and id, so tables with the same columns hash them in the same order
+        for (TypeMetadata { id, layout, .. }, hash) in columns {
+            let size = layout.pad_to_align().size();
+            for (ptr, len) in self.column_segments(id, 0, self.len) {
+                for idx in 0..len {
+                    unsafe { hash(ptr.add(size * idx), state) }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Appends rows from one column of values per type, each copied in with a single memcpy per block of storage
+    pub fn extend_from_columns<C: ColumnBundle>(&mut self, columns: C) {
+        self.check_bundle::<C::Bundle>().unwrap_or_else(|err| panic!("Incompatible bundles used! {err}"));
+        let mut lens = Vec::with_capacity(C::Bundle::METADATA.len());
+        columns.column_lens(|len| lens.push(len));
+        let count = lens.first().copied().unwrap_or(0);
+        assert!(lens.iter().all(|&len| len == count), "Columns must all have the same length, got {lens:?}");
+
+        self.reserve(self.len + count);
+        let (start, end) = (self.len, self.len + count);
+        unsafe {
+            columns.put(|mut src_ptr, type_id| {
+                let size = self.buf.row_info().search_dynamic(type_id).expect("Compatible columns must only contain types in the table").0.layout.size();
+                for (dst_ptr, len) in self.column_segments(type_id, start, end) {
+                    std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, size * len);
+                    src_ptr = src_ptr.add(size * len);
+                }
+            });
+        }
+        self.len = end;
+    }
+
     pub fn extend_from_fn<B: DynamicBundle>(&mut self, n: usize, f: impl FnMut(usize) -> B) {
         self.extend((0..n).map(f))
     }
@@ -188,19 +569,89 @@ impl Table {
     //     todo!()
     // }
 
-    pub fn erase(&self, idx: usize, count: usize) {
-        assert!(idx + count < self.len());
+    // Drops count rows from idx, the rows after them are moved down to keep their order
+    pub fn erase(&mut self, idx: usize, count: usize) {
+        assert!(idx + count <= self.len);
+
+        // closes the gap even if one of the drops panics
+        struct Guard<'a> {
+            table: &'a mut Table,
+            idx: usize,
+            count: usize,
+            tail_len: usize,
+        }
 
-        for drop_idx in idx..(idx + count) {
-            unsafe { self.buf.drop_column(drop_idx) }
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                unsafe { self.table.buf.move_columns(self.idx + self.count, self.tail_len, self.idx) }
+                self.table.len = self.idx + self.tail_len;
+            }
         }
 
-        unsafe { self.buf.move_columns(idx + count, self.len - (idx + count), idx) }
+        let tail_len = self.len - (idx + count);
+        // the erased rows and the tail are forgotten until the guard has moved the tail back
+        self.len = idx;
+        let guard = Guard { table: self, idx, count, tail_len };
+        unsafe { guard.table.buf.drop_columns(idx, idx + count) }
+    }
+}
+
+// FNV-1a with integers written little endian, so checksums agree between builds and platforms as long as the
+// hashed types do. The standard library's hasher is free to change between releases
+#[derive(Copy, Clone, Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) { self.write(&i.to_le_bytes()) }
+    fn write_u32(&mut self, i: u32) { self.write(&i.to_le_bytes()) }
+    fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+    fn write_u128(&mut self, i: u128) { self.write(&i.to_le_bytes()) }
+    // the same on 32 and 64 bit platforms
+    fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+}
+
+// A checksum of every row in tables, e.g. for lockstep peers to detect desyncs. Hashed with StableHasher in table
+// order, see World::checksum to include which entity each row belongs to
+pub fn checksum<'a>(tables: impl IntoIterator<Item = &'a Table>) -> Result<u64, CompareError> {
+    let mut state = StableHasher::default();
+    for table in tables {
+        table.try_hash(&mut state)?;
+    }
+    Ok(state.finish())
+}
+
+impl<B: DynamicBundle> FromIterator<B> for Table {
+    fn from_iter<I: IntoIterator<Item = B>>(iter: I) -> Self {
+        let mut init = Self::new_for_bundle::<B>();
+        init.extend(iter);
+        init
+    }
+}
+
+impl<B: DynamicBundle> Extend<B> for Table {
+    fn extend<I: IntoIterator<Item = B>>(&mut self, iter: I) {
+        Table::extend(self, iter)
     }
 }
 
 impl Drop for Table {
     fn drop(&mut self) {
-        for i in 0..self.len { unsafe { self.buf.drop_column(i) } }
+        unsafe { self.buf.drop_columns(0, self.len) }
     }
 }
@@ -1,44 +1,592 @@
 use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::borrow::Cow;
 use std::marker::PhantomData;
-use crate::storage::Table;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::entity::{Disabled, Entities, Entity, Relation};
+use crate::storage::error::{AccessError, QueryEntityError, QuerySingleError};
+use crate::storage::{Table, TypeMetadata};
+use crate::world::{World, WorldId};
 
-struct Query<Q> {
-    _marker: PhantomData<Q>,
+// Borrows Q's columns in every table that has them, for as long as it lives
+pub struct Query<'a, Q: Accessible> {
+    tables: &'a [Table],
+    entities: &'a Entities,
+    matched: Cow<'a, [MatchedTable]>,
+    accessor: Accessor<'a>,
+    _marker: PhantomData<fn() -> Q>,
 }
 
-struct TypeAccess {
-    is_mutable: bool,
-    type_id: TypeId
+impl<'a, Q: Accessible> Query<'a, Q> {
+    pub(crate) fn new(world: &'a World, include_disabled: bool) -> Result<Self, AccessError> {
+        let accesses = accesses_of::<Q>();
+        let matched = match_tables(world, &accesses, include_disabled, 0..world.table_count());
+        Self::from_parts(world, accesses, Cow::Owned(matched))
+    }
+
+    fn from_parts(world: &'a World, accesses: Vec<TypeAccess>, matched: Cow<'a, [MatchedTable]>) -> Result<Self, AccessError> {
+        let accessor = Accessor::new(world.borrows(), accesses)?;
+        Ok(Self { tables: world.tables(), entities: world.entities(), matched, accessor, _marker: PhantomData })
+    }
+
+    pub fn accesses(&self) -> &[TypeAccess] {
+        self.accessor.accesses()
+    }
+
+    // how many rows the query will visit
+    pub fn count(&self) -> usize {
+        self.matched.iter().map(|matched| self.tables[matched.table].len()).sum()
+    }
+
+    pub fn iter(&mut self) -> impl Iterator<Item = Q::Item<'_>> {
+        matched_blocks(self.tables, &self.matched)
+            .flat_map(|(columns, len)| (0..len).map(move |idx| unsafe {
+                // every row is fetched once, so &mut items never alias
+                Q::fetch(&mut columns.iter().map(|&(ptr, stride)| ptr.add(stride * idx)))
+            }))
+    }
+
+    pub fn for_each(&mut self, f: impl FnMut(Q::Item<'_>)) {
+        self.iter().for_each(f)
+    }
+
+    pub fn get(&self, entity: Entity) -> Result<Q::Item<'_>, QueryEntityError> where Q: ReadOnlyAccessible {
+        unsafe { self.fetch_entity(entity) }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Result<Q::Item<'_>, QueryEntityError> {
+        unsafe { self.fetch_entity(entity) }
+    }
+
+    // Fails unless every entity matches and none is asked for twice
+    pub fn get_many_mut<const N: usize>(&mut self, entities: [Entity; N]) -> Result<[Q::Item<'_>; N], QueryEntityError> {
+        for (idx, entity) in entities.iter().enumerate() {
+            if entities[..idx].contains(entity) {
+                return Err(QueryEntityError::AliasedMutability(*entity));
+            }
+        }
+        let rows = entities.map(|entity| self.locate(entity));
+        if let Some(Err(err)) = rows.iter().find(|row| row.is_err()) {
+            return Err(*err);
+        }
+        // the entities are all different, so are their rows
+        Ok(rows.map(|row| {
+            let (matched, row) = row.expect("Errors were returned above");
+            unsafe { self.fetch_row(matched, row) }
+        }))
+    }
+
+    // The only row of the query, panics unless there is exactly one
+    pub fn single(&mut self) -> Q::Item<'_> {
+        self.get_single().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn get_single(&mut self) -> Result<Q::Item<'_>, QuerySingleError> {
+        let mut rows = self.iter();
+        match (rows.next(), rows.next()) {
+            (None, _) => Err(QuerySingleError::NoEntities),
+            (Some(item), None) => Ok(item),
+            (Some(_), Some(_)) => Err(QuerySingleError::MultipleEntities),
+        }
+    }
+
+    // Every unordered set of K different rows, across all matched tables
+    pub fn iter_combinations<const K: usize>(&self) -> Combinations<'_, Q, K> where Q: ReadOnlyAccessible {
+        Combinations::new(matched_blocks(self.tables, &self.matched).collect())
+    }
+
+    // Like iter_combinations, but rows come back in several combinations, so items are lent one at a time through
+    // Combinations::fetch_next
+    pub fn iter_combinations_mut<const K: usize>(&mut self) -> Combinations<'_, Q, K> {
+        Combinations::new(matched_blocks(self.tables, &self.matched).collect())
+    }
+
+    // Splits the rows of every matched table into batches of at most batch_size and runs f over them on one scoped
+    // thread per core. Batches never share rows, so the accessor's borrows cover every thread at once
+    pub fn par_for_each<F>(&mut self, batch_size: usize, f: F)
+    where
+        F: Fn(Q::Item<'_>) + Sync,
+        for<'b> Q::Item<'b>: Send,
+    {
+        assert!(batch_size > 0, "Batches must have at least one row");
+        let batches: Vec<Batch> = matched_blocks(self.tables, &self.matched)
+            .flat_map(|(columns, len)| (0..len).step_by(batch_size)
+                .map(move |start| Batch { columns: columns.clone(), rows: start..len.min(start + batch_size) }))
+            .collect();
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get).min(batches.len());
+        if threads <= 1 {
+            return self.for_each(f);
+        }
+
+        let next_batch = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    while let Some(batch) = batches.get(next_batch.fetch_add(1, Ordering::Relaxed)) {
+                        for idx in batch.rows.clone() {
+                            f(unsafe { Q::fetch(&mut batch.columns.iter().map(|&(ptr, stride)| ptr.add(stride * idx))) });
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+impl<Q: Accessible> Query<'_, Q> {
+    // the matched table and row of an entity
+    fn locate(&self, entity: Entity) -> Result<(&MatchedTable, usize), QueryEntityError> {
+        let location = self.entities.location(entity).ok_or(QueryEntityError::NoSuchEntity(entity))?;
+        let matched = self.matched.iter().find(|matched| matched.table == location.table)
+            .ok_or(QueryEntityError::QueryDoesNotMatch(entity))?;
+        Ok((matched, location.row))
+    }
+
+    // the caller must make sure the item doesn't alias any other one that is alive
+    unsafe fn fetch_entity<'b>(&self, entity: Entity) -> Result<Q::Item<'b>, QueryEntityError> {
+        let (matched, row) = self.locate(entity)?;
+        Ok(unsafe { self.fetch_row(matched, row) })
+    }
+
+    unsafe fn fetch_row<'b>(&self, matched: &MatchedTable, mut row: usize) -> Q::Item<'b> {
+        for (columns, len) in self.tables[matched.table].column_blocks_at(&*matched.columns) {
+            if row < len {
+                return unsafe { Q::fetch(&mut columns.iter().map(|&(ptr, stride)| ptr.add(stride * row))) };
+            }
+            row -= len;
+        }
+        unreachable!("Entities are always located inside their table")
+    }
+}
+
+// Visits Q for every row with an R, along with J for the row R points at. J is None if the target was despawned or
+// doesn't match. Both sides hold their own borrows, so J can't take anything Q already has
+pub struct JoinQuery<'a, Q: Accessible, R: Relation, J: Accessible> {
+    outer: Query<'a, (Q, &'static R)>,
+    inner: Query<'a, J>,
+}
+
+impl<'a, Q: Accessible, R: Relation, J: Accessible> JoinQuery<'a, Q, R, J> {
+    pub(crate) fn new(world: &'a World, include_disabled: bool) -> Result<Self, AccessError> {
+        let outer = Query::new(world, include_disabled)?;
+        Ok(Self { outer, inner: Query::new(world, include_disabled)? })
+    }
+
+    pub fn count(&self) -> usize {
+        self.outer.count()
+    }
+
+    // Several rows may point at the same target, so its items only live for one call of f
+    pub fn for_each(&mut self, mut f: impl FnMut(Q::Item<'_>, Option<J::Item<'_>>)) {
+        let inner = &self.inner;
+        for (item, relation) in self.outer.iter() {
+            f(item, unsafe { inner.fetch_entity(relation.target()) }.ok())
+        }
+    }
+
+    pub fn iter(&mut self) -> impl Iterator<Item = (Q::Item<'_>, Option<J::Item<'_>>)> where J: ReadOnlyAccessible {
+        let inner = &self.inner;
+        self.outer.iter().map(|(item, relation)| (item, inner.get(relation.target()).ok()))
+    }
+}
+
+// A range of rows of one block, handed to whichever thread gets to it first
+struct Batch {
+    columns: Vec<(*mut u8, usize)>,
+    rows: Range<usize>,
+}
+
+// The pointers are only used to fetch the batch's own rows, and par_for_each requires the items to be Send
+unsafe impl Sync for Batch {}
+
+// Visits the combinations of K rows in lexicographic order of their row indices, counted across all blocks
+pub struct Combinations<'q, Q: Accessible, const K: usize> {
+    blocks: Vec<Block>,
+    // the first row of every block
+    starts: Vec<usize>,
+    row_count: usize,
+    // the rows of the next combination, None once every combination was visited
+    next: Option<[usize; K]>,
+    // the query stays borrowed while combinations are visited
+    _query: PhantomData<&'q mut ()>,
+    _marker: PhantomData<fn() -> Q>,
+}
+
+impl<'q, Q: Accessible, const K: usize> Combinations<'q, Q, K> {
+    fn new(blocks: Vec<Block>) -> Self {
+        assert!(K > 0, "Combinations must have at least one row");
+        let starts: Vec<usize> = blocks.iter().scan(0, |start, &(_, len)| {
+            *start += len;
+            Some(*start - len)
+        }).collect();
+        let row_count = blocks.iter().map(|&(_, len)| len).sum();
+        let next = (K <= row_count).then(|| std::array::from_fn(|idx| idx));
+        Self { blocks, starts, row_count, next, _query: PhantomData, _marker: PhantomData }
+    }
+
+    // Lends the next combination, it has to be dropped before asking for another one
+    pub fn fetch_next(&mut self) -> Option<[Q::Item<'_>; K]> {
+        let rows = self.advance()?;
+        Some(rows.map(|row| unsafe { self.fetch(row) }))
+    }
+
+    fn advance(&mut self) -> Option<[usize; K]> {
+        let rows = self.next?;
+        let mut next = rows;
+        // the last row that can still move right, every row after it restarts just behind it
+        self.next = (0..K).rev().find(|&idx| next[idx] < self.row_count - K + idx).map(|idx| {
+            next[idx] += 1;
+            for after in idx + 1..K {
+                next[after] = next[after - 1] + 1;
+            }
+            next
+        });
+        Some(rows)
+    }
+
+    // rows of one combination are always different, so their items never alias
+    unsafe fn fetch<'a>(&self, row: usize) -> Q::Item<'a> {
+        let block = self.starts.partition_point(|&start| start <= row) - 1;
+        let idx = row - self.starts[block];
+        unsafe { Q::fetch(&mut self.blocks[block].0.iter().map(|&(ptr, stride)| ptr.add(stride * idx))) }
+    }
+}
+
+// Shared items can outlive the next call, so read only combinations are a plain iterator
+impl<'q, Q: ReadOnlyAccessible, const K: usize> Iterator for Combinations<'q, Q, K> {
+    type Item = [Q::Item<'q>; K];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rows = self.advance()?;
+        Some(rows.map(|row| unsafe { self.fetch(row) }))
+    }
+}
+
+// Remembers which tables a query matches, so each run only has to match the tables added or changed since the
+// last one. Panics if used with a world other than the first one it was updated with
+pub struct QueryState<Q: Accessible> {
+    accesses: Vec<TypeAccess>,
+    world: Option<WorldId>,
+    matched: Vec<MatchedTable>,
+    // the world's archetype generation when matched was last brought up to date
+    generation: usize,
+    include_disabled: bool,
+    _marker: PhantomData<fn() -> Q>,
+}
+
+impl<Q: Accessible> QueryState<Q> {
+    pub fn new() -> Self {
+        Self {
+            accesses: accesses_of::<Q>(),
+            world: None,
+            matched: Vec::new(),
+            generation: 0,
+            include_disabled: false,
+            _marker: PhantomData,
+        }
+    }
+
+    // Also matches the tables of disabled entities
+    pub fn including_disabled(self) -> Self {
+        Self { include_disabled: true, world: None, matched: Vec::new(), generation: 0, ..self }
+    }
+
+    pub fn update(&mut self, world: &World) {
+        // NB: matched holds table indices of one world, they mean nothing in another
+        let id = *self.world.get_or_insert(world.id());
+        assert_eq!(id, world.id(), "QueryState used with a different world");
+        let mut changed = world.archetype_changes(self.generation).to_vec();
+        changed.sort_unstable();
+        changed.dedup();
+        // a changed table may have stopped matching, or moved its columns
+        self.matched.retain(|matched| changed.binary_search(&matched.table).is_err());
+        self.matched.extend(match_tables(world, &self.accesses, self.include_disabled, changed));
+        self.generation = world.archetype_generation();
+    }
+
+    pub fn query<'w>(&'w mut self, world: &'w World) -> Result<Query<'w, Q>, AccessError> {
+        self.update(world);
+        Query::from_parts(world, self.accesses.clone(), Cow::Borrowed(&self.matched))
+    }
+
+    // the indices of the tables matched as of the last update
+    pub fn matched_tables(&self) -> impl Iterator<Item = usize> {
+        self.matched.iter().map(|matched| matched.table)
+    }
+}
+
+impl<Q: Accessible> Default for QueryState<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A query built at runtime from a list of accesses, e.g. for editors or scripts. Rows are raw pointers to the
+// accessed columns, in the order of the accesses
+pub struct DynamicQuery<'a> {
+    tables: &'a [Table],
+    matched: Vec<MatchedTable>,
+    accessor: Accessor<'a>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> DynamicQuery<'a> {
+    pub(crate) fn new(world: &'a World, accesses: &[TypeAccess], include_disabled: bool) -> Result<Self, AccessError> {
+        let accessor = Accessor::new(world.borrows(), accesses.iter().copied())?;
+        let matched = match_tables(world, accesses, include_disabled, 0..world.table_count());
+        Ok(Self { tables: world.tables(), matched, accessor, blocks: Vec::new() })
+    }
+
+    pub fn accesses(&self) -> &[TypeAccess] {
+        self.accessor.accesses()
+    }
+
+    pub fn count(&self) -> usize {
+        self.matched.iter().map(|matched| self.tables[matched.table].len()).sum()
+    }
+
+    pub fn iter(&mut self) -> impl Iterator<Item = DynamicRow<'_>> {
+        self.blocks = matched_blocks(self.tables, &self.matched).collect();
+        self.blocks.iter().flat_map(|(columns, len)| (0..*len).map(move |idx| DynamicRow { columns, idx }))
+    }
+
+    pub fn for_each(&mut self, f: impl FnMut(DynamicRow<'_>)) {
+        self.iter().for_each(f)
+    }
+}
+
+// One row of a DynamicQuery. The pointers are only valid while the query is alive, and only for what the
+// matching access allows
+#[derive(Copy, Clone)]
+pub struct DynamicRow<'q> {
+    columns: &'q [(*mut u8, usize)],
+    idx: usize,
+}
+
+impl DynamicRow<'_> {
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    // the column of the access at the same index
+    pub fn get(&self, access_idx: usize) -> *mut u8 {
+        let (ptr, stride) = self.columns[access_idx];
+        unsafe { ptr.add(stride * self.idx) }
+    }
+
+    pub fn ptrs(&self) -> impl Iterator<Item = *mut u8> {
+        (0..self.columns.len()).map(|access_idx| self.get(access_idx))
+    }
+}
+
+// A pointer and stride for each accessed column, and the number of rows they have
+type Block = (Vec<(*mut u8, usize)>, usize);
+
+// A table a query matched, with the position of each accessed column in its row info
+#[derive(Clone, Debug)]
+struct MatchedTable {
+    table: usize,
+    columns: Box<[usize]>,
+}
+
+fn accesses_of<Q: Accessible>() -> Vec<TypeAccess> {
+    let mut accesses = Vec::new();
+    Q::accesses(|access| accesses.push(access));
+    accesses
+}
+
+// Shared by every kind of query: candidates are filtered by signature before their columns are looked up
+fn match_tables(world: &World, accesses: &[TypeAccess], include_disabled: bool, candidates: impl IntoIterator<Item = usize>) -> Vec<MatchedTable> {
+    let Some(signature) = world.signature_for(accesses.iter().map(|access| access.type_id)) else {
+        return Vec::new();
+    };
+    // accessing Disabled opts in as well
+    let disabled = world.signature_for([TypeId::of::<Disabled>()])
+        .filter(|disabled| !include_disabled && !signature.contains_all(disabled));
+    candidates.into_iter()
+        .filter(|&table| world.signature(table).contains_all(&signature))
+        .filter(|&table| disabled.as_ref().is_none_or(|disabled| !world.signature(table).contains_all(disabled)))
+        .map(|table| {
+            let row_info = world.tables()[table].buf.row_info();
+            let columns = accesses.iter().map(|access| row_info.position(access.type_id).expect("The signature matched")).collect();
+            MatchedTable { table, columns }
+        })
+        .collect()
+}
+
+// Every block of rows in the matched tables, with the accessed columns in access order
+fn matched_blocks<'a>(tables: &'a [Table], matched: &'a [MatchedTable]) -> impl Iterator<Item = Block> {
+    matched.iter().flat_map(|matched| tables[matched.table].column_blocks_at(&*matched.columns))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TypeAccess {
+    pub is_mutable: bool,
+    pub type_id: TypeId,
+    // only used for error messages
+    pub name: &'static str,
 }
 
 impl TypeAccess {
-    fn mut_for<A: 'static>() -> Self {
+    pub fn mut_for<A: 'static>() -> Self {
         TypeAccess {
             is_mutable: true,
-            type_id: TypeId::of::<A>()
+            type_id: TypeId::of::<A>(),
+            name: std::any::type_name::<A>(),
+        }
+    }
+
+    pub fn for_metadata(metadata: TypeMetadata, is_mutable: bool) -> Self {
+        TypeAccess {
+            is_mutable,
+            type_id: metadata.id,
+            name: metadata.name,
         }
     }
 
-    fn ref_for<A: 'static>() -> Self {
+    pub fn ref_for<A: 'static>() -> Self {
         TypeAccess {
             is_mutable: false,
-            type_id: TypeId::of::<A>()
+            type_id: TypeId::of::<A>(),
+            name: std::any::type_name::<A>(),
+        }
+    }
+}
+
+// Borrow flags per column type, like a RefCell's: the number of shared borrows, or -1 for an exclusive one
+#[derive(Default)]
+pub struct Borrows {
+    flags: RefCell<HashMap<TypeId, isize>>,
+}
+
+// Holds a set of column borrows until dropped
+pub struct Accessor<'a> {
+    borrows: &'a Borrows,
+    accesses: Box<[TypeAccess]>,
+}
+
+impl<'a> Accessor<'a> {
+    // Takes every borrow or none of them
+    pub fn new(borrows: &'a Borrows, accesses: impl IntoIterator<Item = TypeAccess>) -> Result<Self, AccessError> {
+        let accesses: Box<[TypeAccess]> = accesses.into_iter().collect();
+        for (idx, access) in accesses.iter().enumerate() {
+            if accesses[..idx].iter().any(|other| other.type_id == access.type_id && (other.is_mutable || access.is_mutable)) {
+                return Err(AccessError::Aliased(*access));
+            }
+        }
+
+        let mut flags = borrows.flags.borrow_mut();
+        let conflict = accesses.iter().find(|access| match flags.get(&access.type_id) {
+            Some(&flag) => flag < 0 || access.is_mutable,
+            None => false,
+        });
+        if let Some(&access) = conflict {
+            return Err(AccessError::AlreadyBorrowed(access));
+        }
+        for access in accesses.iter() {
+            *flags.entry(access.type_id).or_insert(0) += if access.is_mutable { -1 } else { 1 };
+        }
+        drop(flags);
+
+        Ok(Self { borrows, accesses })
+    }
+
+    pub fn accesses(&self) -> &[TypeAccess] {
+        &self.accesses
+    }
+}
+
+impl Drop for Accessor<'_> {
+    fn drop(&mut self) {
+        let mut flags = self.borrows.flags.borrow_mut();
+        for access in self.accesses.iter() {
+            let flag = flags.get_mut(&access.type_id).expect("Accessors only release what they borrowed");
+            *flag -= if access.is_mutable { -1 } else { 1 };
+            if *flag == 0 {
+                flags.remove(&access.type_id);
+            }
         }
     }
 }
 
-struct Accessor<'a> {
+/// Something a query can ask for, i.e. &A, &mut A or a tuple of them
+///
+/// # Safety
+/// `accesses` must report every column `fetch` borrows, marked mutable if it's borrowed mutably, and `fetch` must take
+/// exactly one pointer per access in that order, casting it only to the access's type
+pub unsafe trait Accessible {
+    type Item<'a>;
+
+    // every column this reads or writes, in the order fetch takes their pointers
+    fn accesses(f: impl FnMut(TypeAccess));
+
+    /// # Safety
+    /// ptrs must yield a pointer to an initialized value for each access of one row, which may be borrowed as
+    /// accesses describes for 'a
+    unsafe fn fetch<'a>(ptrs: &mut impl Iterator<Item = *mut u8>) -> Self::Item<'a>;
+}
+
+/// Accessible types that only take shared borrows
+///
+/// # Safety
+/// `accesses` must never report a mutable access, so any number of these may fetch the same row at once
+pub unsafe trait ReadOnlyAccessible: Accessible {}
+
+unsafe impl <A: 'static> Accessible for &mut A {
+    type Item<'a> = &'a mut A;
+
+    fn accesses(mut f: impl FnMut(TypeAccess)) { f(TypeAccess::mut_for::<A>()) }
 
+    unsafe fn fetch<'a>(ptrs: &mut impl Iterator<Item = *mut u8>) -> &'a mut A {
+        unsafe { &mut *ptrs.next().expect("A pointer for every access").cast::<A>() }
+    }
 }
 
-trait Accessible {
-    fn access_for() -> TypeAccess;
+unsafe impl <A: 'static> Accessible for &A {
+    type Item<'a> = &'a A;
+
+    fn accesses(mut f: impl FnMut(TypeAccess)) { f(TypeAccess::ref_for::<A>()) }
+
+    unsafe fn fetch<'a>(ptrs: &mut impl Iterator<Item = *mut u8>) -> &'a A {
+        unsafe { &*ptrs.next().expect("A pointer for every access").cast::<A>() }
+    }
 }
 
-impl <'a, A: 'static> Accessible for &'a mut A {
-    fn access_for() -> TypeAccess { TypeAccess::mut_for::<A>() }
+unsafe impl <A: 'static> ReadOnlyAccessible for &A {}
+
+macro_rules! tuple_accessible_impl {
+    ($($accessible:ident),*) => {
+        unsafe impl <$($accessible: Accessible),*> Accessible for ($($accessible,)*) {
+            type Item<'a> = ($($accessible::Item<'a>,)*);
+
+            fn accesses(mut f: impl FnMut(TypeAccess)) {
+                $($accessible::accesses(&mut f);)*
+            }
+
+            unsafe fn fetch<'a>(ptrs: &mut impl Iterator<Item = *mut u8>) -> Self::Item<'a> {
+                unsafe { ($($accessible::fetch(ptrs),)*) }
+            }
+        }
+
+        unsafe impl <$($accessible: ReadOnlyAccessible),*> ReadOnlyAccessible for ($($accessible,)*) {}
+    };
 }
 
-impl <'a, A: 'static> Accessible for &'a A {
-    fn access_for() -> TypeAccess { TypeAccess::ref_for::<A>() }
+macro_rules! all_tuple_accessible_for {
+    ($single:ident) => {
+        tuple_accessible_impl!($single);
+    };
+    ($single:ident, $($list:ident),+) => {
+        tuple_accessible_impl!($single, $($list),+);
+        all_tuple_accessible_for!($($list),+);
+    };
 }
+
+all_tuple_accessible_for!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
@@ -1,41 +1,95 @@
 use crate::storage::type_data::TypeMetadata;
-use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::alloc::{alloc, dealloc, handle_alloc_error, realloc, Layout};
 use std::any::TypeId;
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull;
 
+// Where columns start in memory and how capacity is rounded, e.g. for SIMD over whole columns
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColumnAlignment {
+    // every column starts at a multiple of this (on top of its type's alignment), must be a power of two
+    pub align: usize,
+    // capacity is always a multiple of this many rows
+    pub lanes: usize,
+}
+
+impl ColumnAlignment {
+    pub const NATURAL: Self = Self { align: 1, lanes: 1 };
+    pub const CACHE_LINE: Self = Self { align: 64, lanes: 1 };
+
+    // cache line aligned columns with capacity padded to a whole number of lanes
+    pub const fn simd(lanes: usize) -> Self {
+        Self { align: 64, lanes }
+    }
+
+    pub const fn round_capacity(&self, capacity: usize) -> usize {
+        capacity.next_multiple_of(self.lanes)
+    }
+
+    // The layout of a single column of capacity items
+    pub fn column_layout(&self, layout: Layout, capacity: usize) -> Layout {
+        layout.repeat(capacity)
+            .and_then(|(array_layout, _stride)| array_layout.align_to(self.align))
+            .expect("Could not construct column layout")
+    }
+}
+
+impl Default for ColumnAlignment {
+    fn default() -> Self {
+        Self::NATURAL
+    }
+}
+
 pub struct RawTable {
     data: NonNull<u8>,
     capacity: usize,
-    // non-owning pointers to the data
+    alignment: ColumnAlignment,
+    // each column of the table has its own allocation (grown in place where possible) and data is unused
+    separate_columns: bool,
+    // non-owning pointers to the data, or owning pointers if separate_columns
     rows: RowInfo,
 }
 
 impl RawTable {
-    pub fn new(type_infos: impl IntoIterator<Item = TypeMetadata>) -> Self {
+    pub fn with_alignment(type_infos: impl IntoIterator<Item = TypeMetadata>, alignment: ColumnAlignment) -> Self {
         Self {
             data: NonNull::dangling(),
             capacity: 0,
+            alignment,
+            separate_columns: false,
             rows: RowInfo::new(type_infos)
         }
     }
 
-    pub fn new_unchecked(type_infos: impl IntoIterator<Item = TypeMetadata>) -> Self {
+    pub fn with_alignment_unchecked(type_infos: impl IntoIterator<Item = TypeMetadata>, alignment: ColumnAlignment) -> Self {
         Self {
             data: NonNull::dangling(),
             capacity: 0,
+            alignment,
+            separate_columns: false,
             rows: RowInfo::new_unchecked(type_infos)
         }
     }
 
-    pub unsafe fn from_raw_parts(data: NonNull<u8>, capacity: usize, columns: RowInfo) -> Self {
+    // Every column gets its own allocation, so columns can be added and removed without touching the others
+    pub fn with_separate_columns(type_infos: impl IntoIterator<Item = TypeMetadata>, alignment: ColumnAlignment) -> Self {
         Self {
-            data,
-            capacity,
-            rows: columns
+            data: NonNull::dangling(),
+            capacity: 0,
+            alignment,
+            separate_columns: true,
+            rows: RowInfo::new(type_infos)
         }
     }
 
+    pub fn alignment(&self) -> ColumnAlignment {
+        self.alignment
+    }
+
+    pub fn has_separate_columns(&self) -> bool {
+        self.separate_columns
+    }
+
     // Ensure this table can store at least capacity
     pub fn reserve(&mut self, total_capacity: usize) {
         if total_capacity > self.capacity {
@@ -47,20 +101,33 @@ impl RawTable {
         self.grow_exact(self.capacity.max(min_additional_capacity))
     }
 
-    fn grow_exact(&mut self, additional_capacity: usize) {
-        let mut new_layout = Layout::new::<()>();
-        let mut old_layout = Layout::new::<()>();
-        let mut offsets: Box<[usize]> = std::iter::repeat(0).take(self.rows.len()).collect();
+    // The layout of a single allocation holding every column for capacity rows, and the offset of each column in it
+    pub fn layout_for_capacity(&self, capacity: usize) -> (Layout, Box<[usize]>) {
+        let mut full_layout = Layout::new::<()>();
+        let offsets = self.rows.iter().map(|(TypeMetadata { layout, .. }, _)| {
+            let offset;
+            (full_layout, offset) = full_layout.extend(self.alignment.column_layout(*layout, capacity))
+                .expect("Could not construct layout");
+            offset
+        }).collect();
+        (full_layout.pad_to_align(), offsets)
+    }
 
-        for ((TypeMetadata { layout, .. }, _), offset) in self.rows.iter().zip(offsets.iter_mut()) {
-            (new_layout, *offset) = layout.repeat(self.capacity + additional_capacity)
-                .and_then(|(array_layout, _stride)| new_layout.extend(array_layout))
-                .expect("Could not construct new layout");
-            (old_layout, _) = layout.repeat(self.capacity).and_then(|(array_layout, _stride)| old_layout.extend(array_layout))
-                .expect("Could not construct old layout");
+    fn grow_exact(&mut self, mut additional_capacity: usize) {
+        additional_capacity = self.alignment.round_capacity(self.capacity + additional_capacity) - self.capacity;
+        if self.separate_columns {
+            let (old_capacity, new_capacity) = (self.capacity, self.capacity + additional_capacity);
+            for (TypeMetadata { layout, .. }, ptr) in self.rows.iter_mut() {
+                let old_layout = self.alignment.column_layout(*layout, old_capacity);
+                let new_layout = self.alignment.column_layout(*layout, new_capacity);
+                *ptr = unsafe { reallocate(*ptr, old_layout, new_layout) };
+            }
+            self.capacity = new_capacity;
+            return;
         }
-        new_layout = new_layout.pad_to_align();
-        old_layout = old_layout.pad_to_align();
+
+        let (new_layout, offsets) = self.layout_for_capacity(self.capacity + additional_capacity);
+        let (old_layout, _) = self.layout_for_capacity(self.capacity);
 
         // additional_capacity > 0 therefore, the new layout must be of greater or equal size to the old layout
         debug_assert!(new_layout.size() >= old_layout.size(), "The new layout must be of greater or equal size to the old layout!");
@@ -73,10 +140,10 @@ impl RawTable {
             // SAFETY just checked this invariant
             unsafe { NonNull::new_unchecked(raw_data) }
         } else {
-            new_layout.dangling()
+            new_layout.dangling_ptr()
         };
 
-        for (( TypeMetadata { layout, .. }, ptr ), offset) in self.rows.iter_mut().zip(offsets.into_iter()).rev() {
+        for (( TypeMetadata { layout, .. }, ptr ), offset) in self.rows.iter_mut().zip(offsets).rev() {
             let ptr_in_new_data = unsafe { new_data.add(offset) };
             unsafe { std::ptr::copy_nonoverlapping(ptr.as_ptr(), ptr_in_new_data.as_ptr(), self.capacity * layout.pad_to_align().size()) };
             *ptr = ptr_in_new_data;
@@ -87,39 +154,34 @@ impl RawTable {
     }
     
     pub fn type_metadata(&self) -> impl Iterator<Item = TypeMetadata> {
-        self.rows.iter().map(|&(metadata, _)| metadata)
+        self.rows.type_metadata()
+    }
+
+    pub fn row_info(&self) -> &RowInfo {
+        &self.rows
+    }
+
+    pub fn column_search_dynamic(&self, idx: usize, type_id: TypeId) -> Option<(TypeMetadata, *mut u8)> {
+        self.rows.position(type_id).map(|position| self.column_at(idx, position))
+    }
+
+    // The column at a position in the row info, see RowInfo::position
+    pub fn column_at(&self, idx: usize, position: usize) -> (TypeMetadata, *mut u8) {
+        assert!(idx < self.capacity);
+        let (metadata@TypeMetadata { layout, .. }, data_ptr) = self.rows.get(position);
+        (metadata, unsafe { data_ptr.add(layout.pad_to_align().size() * idx).as_ptr() })
     }
     
+    // NB: Includes zero sized types, which all share a dangling pointer
     pub fn column_iter(&self, idx: usize) -> impl Iterator<Item=(TypeMetadata, *mut u8)> {
         assert!(idx < self.capacity);
         self.rows.iter().cloned().map(move | (metadata@TypeMetadata { layout, .. }, data_ptr) | (metadata, unsafe { data_ptr.add(layout.pad_to_align().size() * idx).as_ptr() }))
+            .chain(self.rows.tags().iter().map(|&metadata| (metadata, metadata.layout.dangling_ptr().as_ptr())))
     }
 
-    pub fn column_iter_range(&self, start: usize, mut len: usize) -> impl Iterator<Item: Iterator<Item=(TypeMetadata, *mut u8)>> {
-        assert!(start + len <= self.capacity);
-        let mut row_iter : Box<[_]> = self.column_iter(start).collect();
-        for (TypeMetadata { layout, .. }, data_ptr) in row_iter.iter_mut() {
-            *data_ptr = unsafe { data_ptr.add(layout.pad_to_align().size() * start) };
-        }
-
-        std::iter::from_fn(move || {
-            if len == 0 {
-                None
-            } else {
-                len -= 1;
-                let output = row_iter.clone().into_iter();
-                for (TypeMetadata { layout, .. }, data_ptr) in row_iter.iter_mut() {
-                    *data_ptr = unsafe { data_ptr.add(layout.pad_to_align().size()) };
-                }
-                Some(output)
-            }
-        })
-    }
-
+    // NB: zero sized types still get dropped once per row
     pub unsafe fn drop_column(&self, idx: usize) {
-        for (TypeMetadata { layout, drop, .. }, data_ptr) in self.rows.iter() {
-            unsafe { drop(data_ptr.add(layout.pad_to_align().size() * idx).as_ptr()) }
-        }
+        unsafe { drop_each(self.column_iter(idx)) }
     }
 
     pub unsafe fn swap_columns(&self, idx_a: usize, idx_b: usize) {
@@ -146,18 +208,42 @@ impl RawTable {
         }
     }
 
+    // Adds a column with uninitialised contents, the caller must initialise it for every live row
+    pub fn add_column(&mut self, metadata: TypeMetadata) {
+        assert!(self.separate_columns || metadata.is_zero_sized(), "Only zero sized types can be added to a table without separate columns");
+        let ptr = unsafe { reallocate(metadata.layout.dangling_ptr(), Layout::new::<()>(), self.alignment.column_layout(metadata.layout, self.capacity)) };
+        self.rows.insert(metadata, ptr);
+    }
+
+    // Removes a column without dropping its contents, the caller must have moved or dropped it for every live row
+    pub fn remove_column(&mut self, type_id: TypeId) -> Option<TypeMetadata> {
+        let (metadata, ptr) = self.rows.search_dynamic(type_id)?;
+        assert!(self.separate_columns || metadata.is_zero_sized(), "Only zero sized types can be removed from a table without separate columns");
+        self.rows.remove(type_id);
+        let layout = self.alignment.column_layout(metadata.layout, self.capacity);
+        if layout.size() > 0 { unsafe { dealloc(ptr.as_ptr(), layout) } }
+        Some(metadata)
+    }
+
     pub unsafe fn clear(&mut self) {
+        if self.separate_columns {
+            for (TypeMetadata { layout, .. }, ptr) in self.rows.iter_mut() {
+                let current_layout = self.alignment.column_layout(*layout, self.capacity);
+                if current_layout.size() > 0 { unsafe { dealloc(ptr.as_ptr(), current_layout); } }
+                *ptr = layout.dangling_ptr();
+            }
+            self.capacity = 0;
+            return;
+        }
+
+        let (current_layout, _) = self.layout_for_capacity(self.capacity);
         let data = std::mem::replace(&mut self.data, NonNull::dangling());
-        let mut current_layout = Layout::new::<()>();
 
         for (TypeMetadata { layout, .. }, ptr) in self.rows.iter_mut() {
-            (current_layout, _) = layout.repeat(self.capacity).and_then(|(array_layout, _stride)| current_layout.extend(array_layout))
-                .expect("Could not construct current layout");
-            *ptr = layout.dangling();
+            *ptr = layout.dangling_ptr();
         }
-        current_layout = current_layout.pad_to_align();
         self.capacity = 0;
-        if current_layout.size() > 0 { dealloc(data.as_ptr(), current_layout); }
+        if current_layout.size() > 0 { unsafe { dealloc(data.as_ptr(), current_layout); } }
     }
 
     pub fn capacity(&self) -> usize {
@@ -167,50 +253,181 @@ impl RawTable {
 
 impl Drop for RawTable {
     fn drop(&mut self) {
-        let mut final_layout = Layout::new::<()>();
-
-        for (TypeMetadata { layout, .. }, .. ) in self.rows.iter() {
-            (final_layout, _) = layout.repeat(self.capacity).and_then(|(array_layout, _stride)| final_layout.extend(array_layout))
-                .expect("Could not construct old layout");
+        if self.separate_columns {
+            unsafe { self.clear() };
+            return;
         }
 
-        final_layout = final_layout.pad_to_align();
+        let (final_layout, _) = self.layout_for_capacity(self.capacity);
 
-        let data = std::mem::replace(&mut self.data, final_layout.dangling());
+        let data = std::mem::replace(&mut self.data, final_layout.dangling_ptr());
         if final_layout.size() > 0 { unsafe { dealloc(data.as_ptr(), final_layout) } }
     }
 }
 
 
-#[repr(transparent)]
+// Moves an allocation to a new layout with the same alignment, either may be zero sized
+unsafe fn reallocate(ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> NonNull<u8> {
+    let raw_data = match (old_layout.size(), new_layout.size()) {
+        (_, 0) => {
+            if old_layout.size() > 0 { unsafe { dealloc(ptr.as_ptr(), old_layout) } }
+            return new_layout.dangling_ptr();
+        }
+        (0, _) => unsafe { alloc(new_layout) },
+        (_, new_size) => unsafe { realloc(ptr.as_ptr(), old_layout, new_size) },
+    };
+    if raw_data.is_null() {
+        handle_alloc_error(new_layout);
+    }
+    // SAFETY just checked this invariant
+    unsafe { NonNull::new_unchecked(raw_data) }
+}
+
+// Drops every value that needs it, if one of the drops panics the rest are still dropped while unwinding
+pub unsafe fn drop_each(values: impl Iterator<Item = (TypeMetadata, *mut u8)>) {
+    struct Guard<I: Iterator<Item = (TypeMetadata, *mut u8)>>(I);
+
+    impl<I: Iterator<Item = (TypeMetadata, *mut u8)>> Drop for Guard<I> {
+        fn drop(&mut self) {
+            // only has anything left to do when unwinding, a second panic aborts
+            for (TypeMetadata { drop, .. }, ptr) in self.0.by_ref() {
+                unsafe { drop(ptr) }
+            }
+        }
+    }
+
+    let mut guard = Guard(values.filter(|(metadata, _)| metadata.needs_drop));
+    for (TypeMetadata { drop, .. }, ptr) in guard.0.by_ref() {
+        unsafe { drop(ptr) }
+    }
+}
+
+// Deref gives only the types with per-row storage, zero sized types are kept separately as tags
 #[derive(Clone)]
-pub struct RowInfo(Box<[(TypeMetadata, NonNull<u8>)]>);
+pub struct RowInfo {
+    rows: Box<[(TypeMetadata, NonNull<u8>)]>,
+    tags: Box<[TypeMetadata]>,
+    // the position of every type sorted by id, as the columns themselves are sorted by name
+    ids: Box<[(TypeId, usize)]>,
+}
 
 impl RowInfo {
     pub fn new(type_metadata: impl IntoIterator<Item = TypeMetadata>) -> Self {
-        let mut inner: Box<[(TypeMetadata, NonNull<u8>)]> = type_metadata.into_iter().map(|metadata| {
-            let ptr = metadata.layout.dangling();
-            (metadata, ptr)
-        }).collect();
-        inner.sort_unstable_by_key(|&(metadata, _)| metadata);
+        let mut output = Self::new_unchecked(type_metadata);
+        // bundle metadata is already sorted at compile time
+        if !output.rows.is_sorted_by_key(|&(metadata, _)| metadata) {
+            output.rows.sort_unstable_by_key(|&(metadata, _)| metadata);
+        }
+        if !output.tags.is_sorted() {
+            output.tags.sort_unstable();
+        }
+        output.index_ids();
         assert!({
             // assert all items are unique
-            inner.windows(2).all(|w| w[0] != w[1])
+            output.rows.windows(2).all(|w| w[0].0 != w[1].0) && output.tags.windows(2).all(|w| w[0] != w[1])
         }, "All item types in a row must be unique!");
-        Self(inner)
+        output
     }
 
     pub fn new_unchecked(type_metadata: impl IntoIterator<Item = TypeMetadata>) -> Self {
-        Self(
-            type_metadata.into_iter().map(|metadata| {
-                let ptr = metadata.layout.dangling();
+        let (tags, rows): (Vec<_>, Vec<_>) = type_metadata.into_iter().partition(TypeMetadata::is_zero_sized);
+        let mut output = Self {
+            rows: rows.into_iter().map(|metadata| {
+                let ptr = metadata.layout.dangling_ptr();
                 (metadata, ptr)
-            }).collect()
-        )
+            }).collect(),
+            tags: tags.into_boxed_slice(),
+            ids: Box::new([]),
+        };
+        output.index_ids();
+        output
+    }
+
+    fn index_ids(&mut self) {
+        let mut ids: Box<[_]> = self.type_metadata().enumerate().map(|(position, metadata)| (metadata.id, position)).collect();
+        ids.sort_unstable();
+        self.ids = ids;
+    }
+
+    // ptr is ignored for zero sized types
+    pub fn insert(&mut self, metadata: TypeMetadata, ptr: NonNull<u8>) {
+        if metadata.is_zero_sized() {
+            let idx = self.tags.binary_search(&metadata).expect_err("All item types in a row must be unique!");
+            let mut tags = std::mem::take(&mut self.tags).into_vec();
+            tags.insert(idx, metadata);
+            self.tags = tags.into_boxed_slice();
+        } else {
+            let idx = self.rows.binary_search_by_key(&metadata, |&(metadata, _)| metadata).expect_err("All item types in a row must be unique!");
+            let mut rows = std::mem::take(&mut self.rows).into_vec();
+            rows.insert(idx, (metadata, ptr));
+            self.rows = rows.into_boxed_slice();
+        }
+        self.index_ids();
+    }
+
+    pub fn remove(&mut self, type_id: TypeId) -> Option<(TypeMetadata, NonNull<u8>)> {
+        let position = self.position(type_id)?;
+        let output = if let Some(idx) = position.checked_sub(self.rows.len()) {
+            let mut tags = std::mem::take(&mut self.tags).into_vec();
+            let output = tags.remove(idx);
+            self.tags = tags.into_boxed_slice();
+            (output, output.layout.dangling_ptr())
+        } else {
+            let mut rows = std::mem::take(&mut self.rows).into_vec();
+            let output = rows.remove(position);
+            self.rows = rows.into_boxed_slice();
+            output
+        };
+        self.index_ids();
+        Some(output)
+    }
+
+    pub fn tags(&self) -> &[TypeMetadata] {
+        &self.tags
+    }
+
+    pub fn type_metadata(&self) -> impl Iterator<Item = TypeMetadata> {
+        self.rows.iter().map(|&(metadata, _)| metadata).chain(self.tags.iter().copied())
     }
 
     pub fn search_dynamic(&self, type_id: TypeId) -> Option<(TypeMetadata, NonNull<u8>)> {
-        self.0.binary_search_by_key(&type_id, |(metadata, _ptr)| metadata.id).ok().map(|idx| self.0[idx])
+        self.position(type_id).map(|position| self.get(position))
+    }
+
+    // Where a type is in type_metadata's order, the same for every table made from the same types.
+    pub fn position(&self, type_id: TypeId) -> Option<usize> {
+        self.ids.binary_search_by_key(&type_id, |&(id, _)| id).ok().map(|idx| self.ids[idx].1)
+    }
+
+    // The position of each type in metadata, or None if the row doesn't have it. Metadata sorted by name (e.g. a
+    // bundle's METADATA) is matched in a single pass, anything out of order falls back to position
+    pub fn positions(&self, metadata: &[TypeMetadata]) -> Vec<Option<usize>> {
+        // Moves cursor up to the first type named like metadata and looks for it among the types with that name
+        fn find_from(sorted: impl Fn(usize) -> Option<TypeMetadata>, cursor: &mut usize, metadata: &TypeMetadata) -> Option<usize> {
+            while sorted(*cursor).is_some_and(|other| other.name < metadata.name) {
+                *cursor += 1;
+            }
+            (*cursor..).map_while(|idx| sorted(idx).filter(|other| other.name == metadata.name).map(|other| (idx, other)))
+                .find_map(|(idx, other)| (other.id == metadata.id).then_some(idx))
+        }
+
+        let (mut row_cursor, mut tag_cursor) = (0, 0);
+        metadata.iter().map(|metadata| {
+            let found = if metadata.is_zero_sized() {
+                find_from(|idx| self.tags.get(idx).copied(), &mut tag_cursor, metadata).map(|idx| self.rows.len() + idx)
+            } else {
+                find_from(|idx| self.rows.get(idx).map(|&(metadata, _)| metadata), &mut row_cursor, metadata)
+            };
+            found.or_else(|| self.position(metadata.id))
+        }).collect()
+    }
+
+    // NB: zero sized types get a dangling pointer
+    pub fn get(&self, position: usize) -> (TypeMetadata, NonNull<u8>) {
+        match position.checked_sub(self.rows.len()) {
+            None => self.rows[position],
+            Some(idx) => (self.tags[idx], self.tags[idx].layout.dangling_ptr()),
+        }
     }
 
     pub fn search<T: 'static>(&self) -> Option<(TypeMetadata, NonNull<u8>)> {
@@ -222,12 +439,12 @@ impl Deref for RowInfo {
     type Target = [(TypeMetadata, NonNull<u8>)];
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.rows
     }
 }
 
 impl DerefMut for RowInfo {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.rows
     }
 }
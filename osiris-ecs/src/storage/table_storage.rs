@@ -0,0 +1,168 @@
+use crate::storage::chunked_table::ChunkedTable;
+use crate::storage::raw_table::{drop_each, ColumnAlignment, RawTable, RowInfo};
+use crate::storage::type_data::TypeMetadata;
+use std::any::TypeId;
+
+// How a table lays out its rows in memory
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StorageMode {
+    // one allocation for the whole table, copied to a bigger one when it grows
+    #[default]
+    Contiguous,
+    // fixed size allocations of at most chunk_size bytes (or one row), rows never move when the table grows
+    Chunked { chunk_size: usize },
+    // one allocation per column, grown in place where the allocator allows and added or removed individually
+    PerColumn,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageOptions {
+    pub mode: StorageMode,
+    pub alignment: ColumnAlignment,
+}
+
+impl From<StorageMode> for StorageOptions {
+    fn from(mode: StorageMode) -> Self {
+        Self { mode, ..Self::default() }
+    }
+}
+
+impl From<ColumnAlignment> for StorageOptions {
+    fn from(alignment: ColumnAlignment) -> Self {
+        Self { alignment, ..Self::default() }
+    }
+}
+
+pub enum TableStorage {
+    Unchunked(RawTable),
+    Chunked(ChunkedTable),
+}
+
+impl TableStorage {
+    pub fn new(type_infos: impl IntoIterator<Item = TypeMetadata>, StorageOptions { mode, alignment }: StorageOptions) -> Self {
+        match mode {
+            StorageMode::Contiguous => Self::Unchunked(RawTable::with_alignment(type_infos, alignment)),
+            StorageMode::PerColumn => Self::Unchunked(RawTable::with_separate_columns(type_infos, alignment)),
+            StorageMode::Chunked { chunk_size } => Self::Chunked(ChunkedTable::new(type_infos, chunk_size, alignment)),
+        }
+    }
+
+    pub fn options(&self) -> StorageOptions {
+        match self {
+            Self::Unchunked(table) if table.has_separate_columns() => StorageOptions { mode: StorageMode::PerColumn, alignment: table.alignment() },
+            Self::Unchunked(table) => StorageOptions { mode: StorageMode::Contiguous, alignment: table.alignment() },
+            Self::Chunked(table) => StorageOptions { mode: StorageMode::Chunked { chunk_size: table.chunk_size() }, alignment: table.alignment() },
+        }
+    }
+
+    // Every block of rows that never moves relative to each other, with how many rows it can hold
+    pub fn chunks(&self) -> impl Iterator<Item = (&RawTable, usize)> {
+        let (contiguous, chunked) = match self {
+            Self::Unchunked(table) => (Some((table, table.capacity())), None),
+            Self::Chunked(table) => (None, Some(table.chunks().iter().map(|chunk| (chunk, table.chunk_capacity())))),
+        };
+        contiguous.into_iter().chain(chunked.into_iter().flatten())
+    }
+
+    pub fn reserve(&mut self, total_capacity: usize) {
+        match self {
+            Self::Unchunked(table) => table.reserve(total_capacity),
+            Self::Chunked(table) => table.reserve(total_capacity),
+        }
+    }
+
+    // whether add_column and remove_column support this type
+    pub fn can_change_columns(&self, metadata: TypeMetadata) -> bool {
+        match self {
+            Self::Unchunked(table) => table.has_separate_columns() || metadata.is_zero_sized(),
+            Self::Chunked(_) => false,
+        }
+    }
+
+    pub fn add_column(&mut self, metadata: TypeMetadata) {
+        match self {
+            Self::Unchunked(table) => table.add_column(metadata),
+            Self::Chunked(_) => panic!("Columns can't be added to a chunked table"),
+        }
+    }
+
+    pub fn remove_column(&mut self, type_id: TypeId) -> Option<TypeMetadata> {
+        match self {
+            Self::Unchunked(table) => table.remove_column(type_id),
+            Self::Chunked(_) => panic!("Columns can't be removed from a chunked table"),
+        }
+    }
+
+    pub fn type_metadata(&self) -> impl Iterator<Item = TypeMetadata> {
+        self.row_info().type_metadata()
+    }
+
+    pub fn row_info(&self) -> &RowInfo {
+        match self {
+            Self::Unchunked(table) => table.row_info(),
+            Self::Chunked(table) => table.row_info(),
+        }
+    }
+
+    pub fn column_search_dynamic(&self, idx: usize, type_id: TypeId) -> Option<(TypeMetadata, *mut u8)> {
+        match self {
+            Self::Unchunked(table) => table.column_search_dynamic(idx, type_id),
+            Self::Chunked(table) => table.column_search_dynamic(idx, type_id),
+        }
+    }
+
+    pub fn column_at(&self, idx: usize, position: usize) -> (TypeMetadata, *mut u8) {
+        match self {
+            Self::Unchunked(table) => table.column_at(idx, position),
+            Self::Chunked(table) => table.column_at(idx, position),
+        }
+    }
+
+    pub fn column_iter(&self, idx: usize) -> impl Iterator<Item=(TypeMetadata, *mut u8)> {
+        let (table, idx) = match self {
+            Self::Unchunked(table) => (table, idx),
+            Self::Chunked(table) => table.locate(idx),
+        };
+        table.column_iter(idx)
+    }
+
+    pub unsafe fn drop_column(&self, idx: usize) {
+        match self {
+            Self::Unchunked(table) => unsafe { table.drop_column(idx) },
+            Self::Chunked(table) => unsafe { table.drop_column(idx) },
+        }
+    }
+
+    // Drops rows start..end, carrying on with the rest if one of them panics
+    pub unsafe fn drop_columns(&self, start: usize, end: usize) {
+        unsafe { drop_each((start..end).flat_map(|idx| self.column_iter(idx))) }
+    }
+
+    pub unsafe fn swap_columns(&self, idx_a: usize, idx_b: usize) {
+        match self {
+            Self::Unchunked(table) => unsafe { table.swap_columns(idx_a, idx_b) },
+            Self::Chunked(table) => unsafe { table.swap_columns(idx_a, idx_b) },
+        }
+    }
+
+    pub unsafe fn move_columns(&self, src_start: usize, src_len: usize, dst_start: usize) {
+        match self {
+            Self::Unchunked(table) => unsafe { table.move_columns(src_start, src_len, dst_start) },
+            Self::Chunked(table) => unsafe { table.move_columns(src_start, src_len, dst_start) },
+        }
+    }
+
+    pub unsafe fn clear(&mut self) {
+        match self {
+            Self::Unchunked(table) => unsafe { table.clear() },
+            Self::Chunked(table) => unsafe { table.clear() },
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        match self {
+            Self::Unchunked(table) => table.capacity(),
+            Self::Chunked(table) => table.capacity(),
+        }
+    }
+}
@@ -1,7 +1,10 @@
 #![cfg(test)]
 
 use crate::storage::Table;
-use std::cell::Cell;
+use crate::entity::{Disabled, Entity, EntityLocation, Parent, Relation};
+use crate::world::{RemovedComponents, Trigger, World};
+use crate::storage::{checksum, AccessError, Bundle, BundleError, CompareError, QueryEntityError, QuerySingleError, QueryState, TypeAccess, ColumnAlignment, DynamicBundle, Nested, StableHasher, StorageMode, StorageOptions, TypeMetadata, TypedTable};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 // Droopy things count how many times they have been dropped
@@ -213,6 +216,1361 @@ fn test_pop() {
     // Congrats, all the items were dropped! And only dropped once!
 }
 
+#[test]
+fn bundle_metadata_is_const() {
+    const METADATA: &[TypeMetadata] = <(u64, u8, Droopy) as DynamicBundle>::METADATA;
+
+    // sorted by name, so the same types in any order have the same metadata
+    assert_eq!(METADATA, &[TypeMetadata::of::<Droopy>(), TypeMetadata::of::<u64>(), TypeMetadata::of::<u8>()]);
+    assert_eq!(METADATA, <(Droopy, u8, u64)>::METADATA);
+}
+
+#[test]
+fn push_pop_unordered_bundle() {
+    let mut sut = Table::new_for_bundle::<(u64, u8, Droopy)>();
+    let counter = Rc::new(Cell::new(0));
+
+    for idx in 0..100 {
+        sut.push((idx as u64, idx as u8, Droopy(idx, counter.clone())));
+    }
+
+    // a bundle with the same types in a different order is still compatible
+    let (droopy, byte, long) = sut.pop::<(Droopy, u8, u64)>();
+    assert_eq!((droopy.0, byte, long), (99, 99, 99));
+    drop(droopy);
+
+    sut.clear();
+    assert_eq!(counter.get(), 100);
+}
+
+#[derive(Bundle)]
+struct Motion {
+    velocity: [f32; 2],
+    droopy: Droopy,
+}
+
+#[derive(Bundle)]
+struct Player {
+    health: u32,
+    #[bundle]
+    motion: Motion,
+    name: &'static str,
+}
+
+#[test]
+fn derived_bundle_metadata() {
+    assert_eq!(Player::METADATA, &[
+        TypeMetadata::of::<&'static str>(),
+        TypeMetadata::of::<[f32; 2]>(),
+        TypeMetadata::of::<Droopy>(),
+        TypeMetadata::of::<u32>(),
+    ]);
+}
+
+#[test]
+fn push_pop_derived_bundle() {
+    let mut sut = Table::new_for_bundle::<Player>();
+    let counter = Rc::new(Cell::new(0));
+
+    for idx in 0..100 {
+        sut.push(Player {
+            health: idx as u32,
+            motion: Motion { velocity: [idx as f32, 0.0], droopy: Droopy(idx, counter.clone()) },
+            name: "droopster",
+        });
+    }
+
+    let Player { health, motion: Motion { velocity, droopy }, name } = sut.pop();
+    assert_eq!((health, velocity, droopy.0, name), (99, [99.0, 0.0], 99, "droopster"));
+    drop(droopy);
+
+    // the derived bundle is interchangeable with a tuple of the same types
+    let (droopy, ..) = sut.pop::<(Droopy, u32, [f32; 2], &'static str)>();
+    assert_eq!(droopy.0, 98);
+    drop(droopy);
+
+    sut.clear();
+    assert_eq!(counter.get(), 100);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Pos(f32, f32);
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Vel(f32, f32);
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Sprite(&'static str);
+
+#[test]
+fn nested_tuple_bundles() {
+    assert_eq!(<Nested<((Pos, Vel), (Sprite,))>>::METADATA, <(Pos, Vel, Sprite)>::METADATA);
+    let mut world = World::new();
+    let idx = world.add_table(Table::new_for_bundle::<Nested<((Pos, Vel), (Sprite,))>>());
+    let ship = world.spawn_in(idx, Nested(((Pos(1.0, 0.0), Vel(0.0, 1.0)), (Sprite("ship"),))));
+    world.spawn_in(idx, Nested(((Sprite("rock"),), (Vel(0.0, 0.0),), (Pos(5.0, 5.0),))));
+    assert_eq!(world.query::<(&Pos,)>().unwrap().get(ship).unwrap(), (&Pos(1.0, 0.0),));
+    assert_eq!(world.table_mut(idx).pop::<Nested<((Pos,), (Vel, Sprite))>>(), Nested(((Pos(5.0, 5.0),), (Vel(0.0, 0.0), Sprite("rock")))));
+
+    // derived bundles are flattened the same way
+    let counter = Rc::new(Cell::new(0));
+    let mut table = Table::new_for_bundle::<Nested<((Pos,), Motion)>>();
+    table.push(Nested(((Pos(0.0, 0.0),), Motion { velocity: [1.0, 2.0], droopy: Droopy(7, counter.clone()) })));
+    let Nested((Motion { velocity, droopy }, (pos,))) = table.pop::<Nested<(Motion, (Pos,))>>();
+    assert_eq!((velocity, droopy.0, pos), ([1.0, 2.0], 7, Pos(0.0, 0.0)));
+    drop(droopy);
+    assert_eq!(counter.get(), 1);
+}
+
+#[test]
+fn tuples_in_tuples_are_components() {
+    // only Nested looks inside its elements, a plain tuple keeps a tuple element as a single column
+    assert_eq!(<((u8, u16), u32)>::METADATA.len(), 2);
+    let mut table = Table::new_for_bundle::<((u8, u16), u32)>();
+    table.push(((1u8, 2u16), 3u32));
+    assert!(table.check_bundle::<(u8, u16, u32)>().is_err());
+    assert_eq!(table.pop::<(u32, (u8, u16))>(), (3, (1, 2)));
+}
+
+#[derive(Bundle)]
+struct Projectile {
+    speed: f32,
+    target: Option<u64>,
+    droopy: Option<Droopy>,
+}
+
+#[test]
+fn optional_bundle_metadata() {
+    assert_eq!(Projectile::METADATA, &[TypeMetadata::of::<f32>(), TypeMetadata::of::<Droopy>(), TypeMetadata::of::<u64>()]);
+    assert_eq!(Projectile::REQUIRED, &[TypeMetadata::of::<f32>()]);
+}
+
+#[test]
+fn push_pop_optional_bundle() {
+    let counter = Rc::new(Cell::new(0));
+    let mut homing = Table::new_for_instance(&Projectile { speed: 0.0, target: Some(0), droopy: None });
+    let mut dumb = Table::new_for_bundle::<(f32, Droopy)>();
+
+    for idx in 0..100 {
+        homing.push(Projectile { speed: idx as f32, target: Some(idx), droopy: None });
+        dumb.push(Projectile { speed: idx as f32, target: None, droopy: Some(Droopy(idx as isize, counter.clone())) });
+    }
+
+    let Projectile { speed, target, droopy } = homing.pop();
+    assert_eq!((speed, target, droopy.is_none()), (99.0, Some(99), true));
+
+    let Projectile { speed, target, droopy } = dumb.pop();
+    assert_eq!((speed, target, droopy.map(|droopy| droopy.0)), (99.0, None, Some(99)));
+
+    drop(dumb);
+    assert_eq!(counter.get(), 100);
+}
+
+#[test]
+#[should_panic]
+fn push_optional_bundle_into_wrong_table() {
+    let mut sut = Table::new_for_bundle::<Projectile>();
+    sut.push(Projectile { speed: 0.0, target: None, droopy: None });
+}
+
+#[test]
+fn strict_bundle_compatibility() {
+    let sut = Table::new_for_bundle::<(u64, u8, Droopy)>();
+
+    assert_eq!(sut.check_bundle::<(Droopy, u64, u8)>(), Ok(()));
+    assert_eq!(sut.check_bundle::<(u64, u8)>(), Err(BundleError::MissingFromBundle(TypeMetadata::of::<Droopy>())));
+    assert_eq!(sut.check_bundle::<(u64, u8, Droopy, f32)>(), Err(BundleError::MissingFromTable(TypeMetadata::of::<f32>())));
+    assert_eq!(sut.check_bundle_subset::<(u64, u8)>(), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "Table column `osiris_ecs::storage::test::Droopy` is missing from the bundle")]
+fn push_subset_bundle() {
+    let mut sut = Table::new_for_bundle::<(u64, u8, Droopy)>();
+    sut.push((0u64, 0u8));
+}
+
+#[test]
+fn pop_partial() {
+    let mut sut = Table::new_for_bundle::<(u64, u8, Droopy)>();
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+
+    for (idx, counter) in data.iter().enumerate() {
+        sut.push((idx as u64, idx as u8, Droopy(idx as isize, counter.clone())));
+    }
+
+    // the un-taken Droopy is dropped along with the row
+    let (long,) = sut.pop_partial::<(u64,)>();
+    assert_eq!(long, 99);
+    assert_eq!(data[99].get(), 1);
+
+    let (droopy, byte) = sut.swap_pop_partial::<(Droopy, u8)>(10);
+    assert_eq!((droopy.0, byte), (10, 10));
+    assert_eq!(data[10].get(), 0);
+    drop(droopy);
+
+    // the last row was swapped into the hole
+    assert_eq!(sut.get_cloned::<(u64, u8)>(10), (98, 98));
+    assert_eq!(sut.len(), 98);
+
+    sut.clear();
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn move_row_between_tables() {
+    let mut src = Table::new_for_bundle::<(u64, u8, Droopy)>();
+    let mut keeps_droopy = Table::new_for_bundle::<(Droopy, u64, f32)>();
+    let mut drops_droopy = Table::new_for_bundle::<(u8,)>();
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+
+    for (idx, counter) in data.iter().enumerate() {
+        src.push((idx as u64, idx as u8, Droopy(idx as isize, counter.clone())));
+    }
+
+    assert_eq!(src.move_row_to(10, &mut keeps_droopy, (1.5f32,)), 0);
+    assert_eq!(data[10].get(), 0);
+    assert_eq!(src.move_row_to(20, &mut drops_droopy, ()), 0);
+    assert_eq!(data[20].get(), 1);
+    // extra replaces the shared column
+    assert_eq!(src.move_row_to(30, &mut drops_droopy, (255u8,)), 1);
+    assert_eq!(data[30].get(), 1);
+
+    assert_eq!(src.len(), 97);
+    assert_eq!(src.get_cloned::<(u64,)>(10), (99,));
+    assert_eq!(keeps_droopy.get_cloned::<(u64, f32)>(0), (10, 1.5));
+    assert_eq!(drops_droopy.get_cloned::<(u8,)>(0), (20,));
+    assert_eq!(drops_droopy.get_cloned::<(u8,)>(1), (255,));
+
+    drop(src);
+    drop(keeps_droopy);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Table column `f32` is missing from the bundle")]
+fn move_row_without_extra() {
+    let mut src = Table::new_for_bundle::<(u64,)>();
+    let mut dst = Table::new_for_bundle::<(u64, f32)>();
+    src.push((0u64,));
+    src.move_row_to(0, &mut dst, ());
+}
+
+#[derive(PartialEq, Hash)]
+struct Enemy;
+
+// A tag that counts its drops, since zero sized types can't hold an Rc
+struct Doomed;
+
+thread_local! {
+    static DOOMED_DROPS: Cell<usize> = const { Cell::new(0) };
+}
+
+impl Drop for Doomed {
+    fn drop(&mut self) {
+        DOOMED_DROPS.with(|drops| drops.update(|x| x + 1))
+    }
+}
+
+#[test]
+fn zero_sized_types_are_tags() {
+    let sut = Table::new_for_bundle::<(Enemy, u64, Doomed)>();
+    let row_info = sut.buf.row_info();
+
+    // u64 is the only column, the tags are positioned after it
+    assert_eq!(row_info.positions(<(Enemy, u64, Doomed)>::METADATA), [Some(1), Some(2), Some(0)]);
+    assert!(row_info.tags().contains(&TypeMetadata::of::<Enemy>()));
+    assert!(row_info.tags().contains(&TypeMetadata::of::<Doomed>()));
+    assert!(row_info.search::<Enemy>().is_some());
+}
+
+#[test]
+fn zero_sized_types_dropped_per_row() {
+    let mut sut = Table::new_for_bundle::<(Enemy, u64, Doomed)>();
+    let counter = Rc::new(Cell::new(0));
+    let mut only_tags = Table::new_for_bundle::<(Doomed, Droopy)>();
+
+    for idx in 0..100 {
+        sut.push((Enemy, idx as u64, Doomed));
+    }
+
+    let (Enemy, long, doomed) = sut.swap_pop::<(Enemy, u64, Doomed)>(0);
+    assert_eq!(long, 0);
+    assert_eq!(DOOMED_DROPS.get(), 0);
+    drop(doomed);
+    assert_eq!(DOOMED_DROPS.get(), 1);
+
+    sut.move_row_to(0, &mut only_tags, (Droopy(0, counter.clone()),));
+    assert_eq!(DOOMED_DROPS.get(), 1);
+
+    sut.swap_remove(0);
+    assert_eq!(DOOMED_DROPS.get(), 2);
+
+    drop(sut);
+    assert_eq!(DOOMED_DROPS.get(), 99);
+    drop(only_tags);
+    assert_eq!(DOOMED_DROPS.get(), 100);
+    assert_eq!(counter.get(), 1);
+}
+
+#[test]
+fn chunked_table_rows_never_move() {
+    const CHUNKED: StorageMode = StorageMode::Chunked { chunk_size: 1024 };
+    let mut sut = Table::with_storage_for_bundle::<(u64, Droopy)>(CHUNKED);
+    let data = std::array::from_fn::<_, 1000, _>(|_| Rc::new(Cell::new(0)));
+
+    assert_eq!(sut.storage_options().mode, CHUNKED);
+    sut.push((0u64, Droopy(0, data[0].clone())));
+    let first_row = sut.column_slices::<u64>().next().unwrap().as_ptr();
+
+    sut.extend(data.iter().enumerate().skip(1).map(|(idx, x)| (idx as u64, Droopy(idx as isize, x.clone()))));
+    assert_eq!(sut.len(), 1000);
+    assert_eq!(sut.column_slices::<u64>().next().unwrap().as_ptr(), first_row);
+
+    // every slice but the last is a full chunk
+    let slices: Vec<_> = sut.column_slices::<u64>().collect();
+    assert!(slices.len() > 1);
+    assert!(slices.iter().rev().skip(1).all(|slice| slice.len() == slices[0].len()));
+    assert!(slices.iter().flat_map(|slice| slice.iter()).copied().eq(0..1000));
+
+    for droopster in 0..500 {
+        sut.swap_remove(droopster);
+    }
+    for (idx, data) in data.iter().take(500).enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+
+    for value in sut.column_slices_mut::<u64>().flatten() {
+        *value += 1;
+    }
+    let (long, droopy) = sut.pop::<(u64, Droopy)>();
+    assert_eq!((long, droopy.0), (501, 500));
+    drop(droopy);
+
+    sut.clear();
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn move_row_between_storage_modes() {
+    let mut src = Table::with_storage_for_bundle::<(u64, u8)>(StorageMode::Chunked { chunk_size: 64 });
+    let mut dst = Table::new_for_bundle::<(u64, f32)>();
+
+    src.extend((0..100).map(|idx| (idx as u64, idx as u8)));
+    for idx in (0..100).step_by(2) {
+        dst.push((idx as u64, 0.0f32));
+    }
+    src.move_row_to(50, &mut dst, (1.0f32,));
+
+    assert_eq!(dst.get_cloned::<(u64, f32)>(50), (50, 1.0));
+    assert_eq!(src.get_cloned::<(u64, u8)>(50), (99, 99));
+}
+
+#[test]
+fn simd_aligned_columns() {
+    for mode in [StorageMode::Contiguous, StorageMode::Chunked { chunk_size: 1000 }] {
+        let mut sut = Table::with_storage_for_bundle::<(u8, f32, Enemy)>(StorageOptions { mode, alignment: ColumnAlignment::simd(8) });
+        sut.extend((0..100).map(|idx| (idx as u8, idx as f32, Enemy)));
+
+        assert_eq!(sut.capacity() % 8, 0);
+        for slice in sut.column_slices::<u8>() {
+            assert_eq!(slice.as_ptr().addr() % 64, 0);
+        }
+
+        for (batches, remainder) in sut.column_chunks_mut::<f32, 8>() {
+            for batch in batches {
+                assert_eq!(batch.as_ptr().addr() % 32, 0);
+                for value in batch {
+                    *value *= 2.0;
+                }
+            }
+            for value in remainder {
+                *value *= 2.0;
+            }
+        }
+
+        let (remainders, count) = sut.column_chunks::<f32, 8>()
+            .fold((0, 0), |(remainders, count), (batches, remainder)| (remainders + remainder.len(), count + batches.len() * 8 + remainder.len()));
+        assert_eq!((remainders, count), (100 % 8, 100));
+        assert!(sut.column_slices::<f32>().flatten().copied().eq((0..100).map(|idx| idx as f32 * 2.0)));
+    }
+}
+
+#[test]
+fn per_column_table_add_remove_columns() {
+    let mut sut = Table::with_storage_for_bundle::<(u64, Droopy)>(StorageMode::PerColumn);
+    let data = std::array::from_fn::<_, 1000, _>(|_| Rc::new(Cell::new(0)));
+
+    assert_eq!(sut.storage_options().mode, StorageMode::PerColumn);
+    sut.extend(data.iter().enumerate().map(|(idx, x)| (idx as u64, Droopy(idx as isize, x.clone()))));
+
+    sut.add_column(|idx| idx as f32 * 0.5);
+    sut.add_column(|_| Enemy);
+    assert_eq!(sut.check_bundle::<(Enemy, f32, u64, Droopy)>(), Ok(()));
+    assert!(sut.column_slices::<f32>().flatten().copied().eq((0..1000).map(|idx| idx as f32 * 0.5)));
+
+    // the new columns take part in growth and row operations like any other
+    sut.push((1000u64, Droopy(1000, Rc::new(Cell::new(0))), 500.0f32, Enemy));
+    sut.swap_remove(0);
+    assert_eq!(data[0].get(), 1);
+    assert_eq!(sut.get_cloned::<(u64, f32)>(0), (1000, 500.0));
+
+    let droopies = sut.remove_column::<Droopy>();
+    assert_eq!(droopies.len(), 1000);
+    assert_eq!(droopies[0].0, 1000);
+    assert_eq!(sut.check_bundle::<(Enemy, f32, u64)>(), Ok(()));
+
+    drop(sut);
+    assert!(data.iter().skip(1).all(|data| data.get() == 0));
+    drop(droopies);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Columns of `f32` can't be added to this table")]
+fn contiguous_table_add_column() {
+    let mut sut = Table::new_for_bundle::<(u64,)>();
+    sut.add_column(|_| Enemy);
+    sut.add_column(|_| 0.0f32);
+}
+
+// Counts its drops like a Droopy, but panics while being dropped when armed
+struct Volatile(Droopy, bool);
+
+impl Drop for Volatile {
+    fn drop(&mut self) {
+        if self.1 {
+            panic!("Volatile {} went off", self.0.0);
+        }
+    }
+}
+
+fn volatile_table(data: &[Rc<Cell<usize>>], armed: usize) -> Table {
+    Table::from_iter(data.iter().enumerate().map(|(idx, x)| (Volatile(Droopy(idx as isize, x.clone()), idx == armed), Droopy(idx as isize, x.clone()))))
+}
+
+fn catch_panic(f: impl FnOnce()) {
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_err(), "Expected a panic");
+}
+
+#[test]
+fn clear_with_panicking_drop() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut sut = volatile_table(&data, 50);
+
+    catch_panic(|| sut.clear());
+    assert_eq!(sut.len(), 0);
+
+    // both columns of every row were dropped, including the rest of the row that panicked
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 2, "Value at {} is false!", idx);
+    }
+
+    sut.extend_from_fn(10, |idx| (Volatile(Droopy(idx as isize, data[idx].clone()), false), Droopy(idx as isize, data[idx].clone())));
+    drop(sut);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), if idx < 10 { 4 } else { 2 }, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn drop_with_panicking_drop() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let sut = volatile_table(&data, 0);
+
+    catch_panic(|| drop(sut));
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 2, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn swap_remove_with_panicking_drop() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut sut = volatile_table(&data, 10);
+
+    catch_panic(|| sut.swap_remove(10));
+    assert_eq!(sut.len(), 99);
+    assert_eq!(data[10].get(), 2);
+    assert_eq!(sut.column_slices::<Droopy>().flatten().nth(10).unwrap().0, 99);
+
+    drop(sut);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 2, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn test_erase() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut sut = Table::from_fn(100, |idx| (Droopy(idx as isize, data[idx].clone()),));
+
+    sut.erase(10, 20);
+    assert_eq!(sut.len(), 80);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), (10..30).contains(&idx) as usize, "Value at {} is false!", idx);
+    }
+    // the tail keeps its order
+    for (idx, droopy) in sut.column_slices::<Droopy>().flatten().enumerate() {
+        assert_eq!(droopy.0, if idx < 10 { idx } else { idx + 20 } as isize);
+    }
+
+    sut.erase(70, 10);
+    assert_eq!(sut.len(), 70);
+
+    drop(sut);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn erase_with_panicking_drop() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut sut = volatile_table(&data, 15);
+
+    catch_panic(|| sut.erase(10, 20));
+    assert_eq!(sut.len(), 80);
+    for (idx, droopy) in sut.column_slices::<Droopy>().flatten().enumerate() {
+        assert_eq!(droopy.0, if idx < 10 { idx } else { idx + 20 } as isize);
+    }
+
+    drop(sut);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 2, "Value at {} is false!", idx);
+    }
+}
+
+// Every row's entity still points back at it
+fn assert_entities_in_sync(world: &World, table: usize) {
+    for (row, &entity) in world.row_entities(table).iter().enumerate() {
+        assert_eq!(world.entities().location(entity), Some(EntityLocation { table, row }));
+    }
+}
+
+#[test]
+fn world_remove_with_panicking_drop() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut world = World::new();
+    let idx = world.add_table(volatile_table(&data[..50], 15));
+    let entities = world.row_entities(idx).to_vec();
+
+    // the row is gone even though its drop panicked, and the last row took its place
+    catch_panic(|| { world.despawn(entities[15]); });
+    assert!(!world.entities().contains(entities[15]));
+    assert_eq!(world.row_entities(idx)[15], entities[49]);
+    assert_entities_in_sync(&world, idx);
+    assert_eq!(world.query::<(&Droopy,)>().unwrap().get(entities[49]).unwrap().0.0, 49);
+
+    let idx = world.add_table(volatile_table(&data[50..], 15));
+    let entities = world.row_entities(idx).to_vec();
+    catch_panic(|| world.table_mut(idx).erase(10, 20));
+    assert_eq!(world.row_entities(idx), [&entities[..10], &entities[30..]].concat());
+    assert!(entities[10..30].iter().all(|&entity| !world.entities().contains(entity)));
+    assert_entities_in_sync(&world, idx);
+
+    drop(world);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 2, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn extend_with_panicking_iterator() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut sut = Table::new_for_bundle::<(Droopy,)>();
+
+    catch_panic(|| sut.extend(data.iter().enumerate().map(|(idx, x)| {
+        assert!(idx < 50, "Iterator gave up");
+        (Droopy(idx as isize, x.clone()),)
+    })));
+    // everything written before the panic is kept
+    assert_eq!(sut.len(), 50);
+
+    drop(sut);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), (idx < 50) as usize, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn move_row_to_with_panicking_drop() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let mut src = volatile_table(&data, 50);
+    let mut dst = Table::new_for_bundle::<(Droopy,)>();
+
+    catch_panic(|| { src.move_row_to(50, &mut dst, ()); });
+    assert_eq!(src.len(), 99);
+    assert_eq!(dst.len(), 1);
+    assert_eq!(data[50].get(), 1);
+    assert_eq!(dst.column_slices::<Droopy>().flatten().next().unwrap().0, 50);
+
+    drop(src);
+    drop(dst);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 2, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn typed_table_iter() {
+    let data = std::array::from_fn::<_, 1000, _>(|_| Rc::new(Cell::new(0)));
+    let mut sut = TypedTable::<(u32, Droopy, Enemy)>::with_storage(StorageMode::Chunked { chunk_size: 1024 });
+    sut.extend(data.iter().enumerate().map(|(idx, x)| (idx as u32, Droopy(idx as isize, x.clone()), Enemy)));
+    assert_eq!(sut.len(), 1000);
+
+    for (value, droopy) in sut.iter_mut().map(|(value, droopy, _)| (value, droopy)) {
+        *value += 1;
+        droopy.0 *= 2;
+    }
+    for (idx, (value, droopy, Enemy)) in sut.iter().enumerate() {
+        assert_eq!(*value, idx as u32 + 1);
+        assert_eq!(droopy.0, idx as isize * 2);
+    }
+
+    let (value, droopy, _) = sut.swap_pop(0);
+    assert_eq!((value, droopy.0), (1, 0));
+    drop(droopy);
+    assert_eq!(sut.iter().next().unwrap().0, &1000);
+
+    drop(sut);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn typed_table_conversion() {
+    let table = Table::from_fn(10, |idx| (idx as u64, idx as f32));
+
+    // the table is handed back when B doesn't match
+    let (table, err) = TypedTable::<(u64,)>::from_table(table).err().unwrap();
+    assert_eq!(err, BundleError::MissingFromBundle(TypeMetadata::of::<f32>()));
+
+    let mut sut = TypedTable::<(f32, u64)>::from_table(table).ok().unwrap();
+    sut.push((10.0, 10));
+    assert_eq!(sut.iter().map(|(value, _)| *value).sum::<f32>(), 55.0);
+
+    let table: Table = sut.into();
+    assert_eq!(table.len(), 11);
+    assert_eq!(table.get_cloned::<(u64,)>(10), (10,));
+
+    let mut projectiles = TypedTable::<Projectile>::from_table(Table::new_for_bundle::<Projectile>()).ok().unwrap();
+    projectiles.push(Projectile { speed: 1.0, target: Some(2), droopy: Some(Droopy(3, Rc::new(Cell::new(0)))) });
+    assert_eq!(projectiles.pop().target, Some(2));
+}
+
+#[test]
+fn table_into_iter() {
+    let data = std::array::from_fn::<_, 100, _>(|_| Rc::new(Cell::new(0)));
+    let sut: Table = data.iter().enumerate().map(|(idx, x)| (idx as u64, Droopy(idx as isize, x.clone()))).collect();
+
+    let mut iter = sut.into_iter::<(Droopy, u64)>();
+    assert_eq!(iter.len(), 100);
+    for idx in 0..50 {
+        let (droopy, value) = iter.next().unwrap();
+        assert_eq!((droopy.0, value), (idx as isize, idx as u64));
+    }
+    let (droopy, _) = iter.next_back().unwrap();
+    assert_eq!(droopy.0, 99);
+    assert_eq!(iter.len(), 49);
+    assert!(data.iter().take(50).all(|data| data.get() == 1));
+
+    // the rows that weren't yielded are dropped with the iterator
+    drop(iter);
+    drop(droopy);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+fn table_from_iter_and_extend() {
+    let mut sut: Table = (0..10u64).map(|idx| (idx, idx as f32)).collect();
+    // any iterator works, not just ones with an exact size
+    Extend::extend(&mut sut, (10..100u64).filter(|idx| idx % 2 == 0).map(|idx| (idx, idx as f32)));
+    sut.extend(std::iter::successors(Some(100u64), |idx| (*idx < 109).then_some(idx + 1)).map(|idx| (idx as f32, idx)));
+    assert_eq!(sut.len(), 10 + 45 + 10);
+
+    let values: Vec<u64> = sut.into_iter::<(u64, f32)>().map(|(value, _)| value).collect();
+    assert_eq!(values, (0..10).chain((10..100).step_by(2)).chain(100..110).collect::<Vec<_>>());
+
+    let typed: TypedTable<(u64,)> = (0..10u64).map(|idx| (idx,)).collect();
+    assert_eq!(typed.into_iter().rev().map(|(value,)| value).collect::<Vec<_>>(), (0..10).rev().collect::<Vec<_>>());
+}
+
+#[test]
+#[should_panic(expected = "Table column `f32` is missing from the bundle")]
+fn into_iter_wrong_bundle() {
+    let sut = Table::from_fn(10, |idx| (idx as u64, idx as f32));
+    let _ = sut.into_iter::<(u64,)>();
+}
+
+#[test]
+fn extend_from_columns() {
+    let data = std::array::from_fn::<_, 1000, _>(|_| Rc::new(Cell::new(0)));
+    let values: Vec<u64> = (0..1000).collect();
+
+    for mode in [StorageMode::Contiguous, StorageMode::Chunked { chunk_size: 1000 }, StorageMode::PerColumn] {
+        let mut sut = Table::with_storage_for_bundle::<(u64, Droopy, Enemy)>(mode);
+        sut.push((1000u64, Droopy(-1, Rc::new(Cell::new(0))), Enemy));
+
+        let droopies = data.iter().enumerate().map(|(idx, x)| Droopy(idx as isize, x.clone())).collect::<Vec<_>>();
+        sut.extend_from_columns((droopies, values.as_slice(), (0..1000).map(|_| Enemy).collect::<Vec<_>>()));
+        assert_eq!(sut.len(), 1001);
+        assert!(data.iter().all(|data| data.get() == 0), "Moved values must not be dropped");
+
+        for (idx, (value, droopy)) in std::iter::zip(sut.column_slices::<u64>().flatten(), sut.column_slices::<Droopy>().flatten()).skip(1).enumerate() {
+            assert_eq!(*value, idx as u64);
+            assert_eq!(droopy.0, idx as isize);
+        }
+
+        sut.clear();
+        for (idx, data) in data.iter().enumerate() {
+            assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+            data.set(0);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "Columns must all have the same length")]
+fn extend_from_uneven_columns() {
+    let mut sut = Table::new_for_bundle::<(u64, f32)>();
+    sut.extend_from_columns((vec![0u64; 10], [0.0f32; 9].as_slice()));
+}
+
+#[test]
+fn append_split_off_and_merge() {
+    let data = std::array::from_fn::<_, 1000, _>(|_| Rc::new(Cell::new(0)));
+    let droopies = |range: std::ops::Range<usize>| data[range.clone()].iter().zip(range).map(|(x, idx)| (idx as u64, Droopy(idx as isize, x.clone()), Enemy)).collect::<Vec<_>>();
+
+    // every storage mode has its own layout, so runs are split differently on each side
+    let mut sut = Table::with_storage_for_bundle::<(u64, Droopy, Enemy)>(StorageMode::Chunked { chunk_size: 700 });
+    sut.extend(droopies(0..100));
+    let mut other = Table::with_storage_for_bundle::<(Enemy, Droopy, u64)>(StorageMode::PerColumn);
+    other.extend(droopies(100..300));
+    sut.append(&mut other);
+    assert_eq!((sut.len(), other.len()), (300, 0));
+
+    let workers = [300..500, 500..800, 800..1000].map(|range| {
+        let mut table = Table::with_storage_for_bundle::<(u64, Droopy, Enemy)>(StorageMode::Chunked { chunk_size: 1000 });
+        table.extend(droopies(range));
+        table
+    });
+    let mut sut = sut.merge(workers);
+    assert_eq!(sut.len(), 1000);
+
+    let tail = sut.split_off(250);
+    assert_eq!((sut.len(), tail.len()), (250, 750));
+    assert_eq!(tail.storage_options().mode, StorageMode::Chunked { chunk_size: 700 });
+    assert!(data.iter().all(|data| data.get() == 0), "Moved values must not be dropped");
+
+    for (idx, (value, droopy)) in std::iter::zip(sut.column_slices::<u64>().chain(tail.column_slices::<u64>()).flatten(), sut.column_slices::<Droopy>().chain(tail.column_slices::<Droopy>()).flatten()).enumerate() {
+        assert_eq!((*value, droopy.0), (idx as u64, idx as isize));
+    }
+
+    drop(sut);
+    drop(tail);
+    drop(other);
+    for (idx, data) in data.iter().enumerate() {
+        assert_eq!(data.get(), 1, "Value at {} is false!", idx);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Incompatible tables used! Bundle type `f32` is missing from the table")]
+fn append_incompatible_table() {
+    let mut sut = Table::from_fn(10, |idx| (idx as u64,));
+    sut.append(&mut Table::from_fn(10, |idx| (idx as u64, idx as f32)));
+}
+
+fn comparable_table(mode: StorageMode) -> Table {
+    let types = [TypeMetadata::of::<u64>().with_eq::<u64>().with_hash::<u64>(), TypeMetadata::of::<u8>().with_eq::<u8>().with_hash::<u8>(), TypeMetadata::of::<Enemy>()];
+    let mut table = Table::with_storage(types, mode);
+    table.extend((0..100).map(|idx| (idx as u8, Enemy, idx as u64 * 3)));
+    table
+}
+
+#[test]
+fn table_eq_and_hash() {
+    let hash = |table: &Table| {
+        let mut state = std::hash::DefaultHasher::new();
+        table.try_hash(&mut state).map(|()| std::hash::Hasher::finish(&state))
+    };
+
+    let mut sut = comparable_table(StorageMode::Contiguous);
+    let other = comparable_table(StorageMode::Chunked { chunk_size: 100 });
+    assert_eq!(sut.try_eq(&other), Ok(true));
+    assert_eq!(hash(&sut), hash(&other));
+    assert_eq!(checksum([&sut, &other]), checksum([&other, &sut]));
+
+    *sut.column_slices_mut::<u64>().flatten().nth(50).unwrap() += 1;
+    assert_eq!(sut.try_eq(&other), Ok(false));
+    assert_ne!(hash(&sut), hash(&other));
+
+    sut.pop::<(u64, u8, Enemy)>();
+    assert_eq!(sut.try_eq(&other), Ok(false));
+}
+
+#[test]
+fn table_eq_and_hash_need_metadata() {
+    let plain = Table::from_fn(10, |idx| (idx as u64, idx as u8, Enemy));
+    let comparable = comparable_table(StorageMode::PerColumn);
+
+    // columns are checked in name order
+    assert!(matches!(plain.try_eq(&comparable), Err(CompareError::MissingEq(metadata)) if metadata.name == "u64"));
+    assert!(matches!(checksum([&comparable, &plain]), Err(CompareError::MissingHash(metadata)) if metadata.name == "u64"));
+    assert_eq!(comparable.try_eq(&Table::from_fn(10, |idx| (idx as u64,))), Err(CompareError::Incompatible(BundleError::MissingFromBundle(TypeMetadata::of::<u8>()))));
+}
+
+#[derive(Bundle)]
+#[bundle(eq, hash)]
+struct Unit {
+    id: u64,
+    rank: Option<u8>,
+    enemy: Enemy,
+}
+
+#[test]
+fn derived_bundle_eq_and_hash() {
+    let units = || {
+        let mut table = Table::new_for_bundle::<Unit>();
+        table.extend((0..10).map(|idx| Unit { id: idx, rank: Some(idx as u8), enemy: Enemy }));
+        table
+    };
+    let mut sut = units();
+    assert_eq!(sut.try_eq(&units()), Ok(true));
+    assert_eq!(checksum([&sut]), checksum([&units()]));
+
+    sut.insert_at(3, Unit { id: 3, rank: Some(0), enemy: Enemy });
+    assert_eq!(sut.try_eq(&units()), Ok(false));
+    assert_ne!(checksum([&sut]), checksum([&units()]));
+}
+
+#[test]
+fn world_checksum() {
+    // FNV-1a's reference value, integers are written little endian on every platform
+    let mut state = StableHasher::default();
+    std::hash::Hasher::write(&mut state, b"a");
+    assert_eq!(std::hash::Hasher::finish(&state), 0xaf63_dc4c_8601_ec8c);
+
+    let world = || {
+        let mut world = World::new();
+        world.add_table(comparable_table(StorageMode::Contiguous));
+        world
+    };
+    let mut sut = world();
+    assert_eq!(sut.checksum(), world().checksum());
+
+    // the same rows, but the last one now belongs to a new entity
+    let row = sut.table_mut(0).pop::<(u64, u8, Enemy)>();
+    sut.table_mut(0).push(row);
+    assert_eq!(checksum([&*sut.table_mut(0)]), checksum([&*world().table_mut(0)]));
+    assert_ne!(sut.checksum(), world().checksum());
+
+    sut.add_table(Table::from_fn(10, |idx| (idx as u64,)));
+    assert!(matches!(sut.checksum(), Err(CompareError::MissingHash(metadata)) if metadata.name == "u64"));
+}
+
+fn query_world() -> World {
+    let mut world = World::new();
+    world.add_table(Table::from_fn(100, |idx| (idx as u64, idx as f32)));
+    world.add_table(Table::from_fn(50, |idx| (idx as u64, idx as u8, Enemy)));
+    world.add_table(Table::from_fn(10, |idx| (idx as f32,)));
+    world
+}
+
+#[test]
+fn query_iter() {
+    let world = query_world();
+    let mut query = world.query::<(&mut u64, &f32)>().unwrap();
+    assert_eq!(query.count(), 100);
+    for (value, float) in query.iter() {
+        *value += *float as u64;
+    }
+    drop(query);
+
+    // only tables with every accessed column are visited
+    let mut query = world.query::<(&u64,)>().unwrap();
+    assert_eq!(query.count(), 150);
+    let mut values: Vec<u64> = query.iter().map(|(value,)| *value).collect();
+    let mut expected: Vec<u64> = (0..50).chain((0..100).map(|idx| idx * 2)).collect();
+    values.sort();
+    expected.sort();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn query_borrow_conflicts() {
+    let world = query_world();
+    assert_eq!(world.query::<(&mut u64, &u64)>().err(), Some(AccessError::Aliased(TypeAccess::ref_for::<u64>())));
+    assert_eq!(world.query::<(&mut u64, &mut u64)>().err(), Some(AccessError::Aliased(TypeAccess::mut_for::<u64>())));
+
+    let shared = world.query::<(&u64, &f32)>().unwrap();
+    let also_shared = world.query::<(&u64, &u64)>().unwrap();
+    assert_eq!(world.query::<(&mut f32,)>().err(), Some(AccessError::AlreadyBorrowed(TypeAccess::mut_for::<f32>())));
+    drop(shared);
+    assert_eq!(world.query::<(&u8, &mut u64)>().err(), Some(AccessError::AlreadyBorrowed(TypeAccess::mut_for::<u64>())));
+    // a failed query takes none of its borrows
+    let exclusive = world.query::<(&mut f32, &u8)>().unwrap();
+    assert_eq!(world.query::<(&f32,)>().err(), Some(AccessError::AlreadyBorrowed(TypeAccess::ref_for::<f32>())));
+    assert_eq!(AccessError::AlreadyBorrowed(TypeAccess::ref_for::<f32>()).to_string(), "Can't borrow `f32`, it is already borrowed mutably");
+
+    drop(also_shared);
+    drop(exclusive);
+    assert!(world.query::<(&mut u64, &mut f32, &mut u8)>().is_ok());
+}
+
+#[test]
+fn dynamic_query() {
+    let world = query_world();
+    let accesses = [TypeAccess::for_metadata(TypeMetadata::of::<f32>(), false), TypeAccess::mut_for::<u64>()];
+    let mut query = world.dynamic_query(&accesses).unwrap();
+    assert_eq!(query.count(), 100);
+    assert_eq!(world.query::<(&u64,)>().err(), Some(AccessError::AlreadyBorrowed(TypeAccess::ref_for::<u64>())));
+
+    query.for_each(|row| unsafe {
+        assert_eq!(row.len(), 2);
+        *row.get(1).cast::<u64>() += *row.get(0).cast::<f32>() as u64 + 1;
+    });
+    drop(query);
+
+    let mut query = world.query::<(&u64, &f32)>().unwrap();
+    assert!(query.iter().all(|(value, float)| *value == *float as u64 * 2 + 1));
+}
+
+#[test]
+fn query_state_matches_new_tables() {
+    let mut world = query_world();
+    let mut state = QueryState::<(&u64, &mut f32)>::new();
+    assert_eq!(state.query(&world).unwrap().count(), 100);
+    assert_eq!(world.archetype_generation(), 3);
+
+    // only the new table is matched on the next run
+    world.add_table(Table::from_fn(20, |idx| (idx as f32, idx as u64, idx as u8)));
+    world.add_table(Table::from_fn(5, |idx| (idx as u8,)));
+    let mut query = state.query(&world).unwrap();
+    assert_eq!(query.count(), 120);
+    query.for_each(|(value, float)| *float += *value as f32);
+    drop(query);
+    assert_eq!(state.matched_tables().collect::<Vec<_>>(), [0, 3]);
+
+    // changing rows keeps the signature
+    world.table_mut(0).push((7u64, 1.0f32));
+    assert_eq!(world.archetype_generation(), 5);
+    let mut query = state.query(&world).unwrap();
+    assert_eq!(query.count(), 121);
+    assert!(query.iter().all(|(value, float)| *float == *value as f32 * 2.0 || (*value, *float) == (7, 1.0)));
+}
+
+#[test]
+fn query_state_rematches_changed_tables() {
+    let mut world = query_world();
+    let mut table = Table::with_storage_for_bundle::<(u64, u8)>(StorageMode::PerColumn);
+    table.extend((0..50).map(|idx| (idx as u64, idx as u8)));
+    let idx = world.add_table(table);
+    let mut state = QueryState::<(&u8, &f32)>::new();
+    assert_eq!(state.query(&world).unwrap().count(), 0);
+
+    world.table_mut(idx).add_column(|idx| idx as f32 * 0.5);
+    let mut query = state.query(&world).unwrap();
+    assert_eq!(query.count(), 50);
+    assert!(query.iter().all(|(value, float)| *float == *value as f32 * 0.5));
+    drop(query);
+
+    world.table_mut(idx).remove_column::<u8>();
+    assert_eq!(state.query(&world).unwrap().count(), 0);
+    assert_eq!(world.archetype_generation(), 6);
+
+    // the cached matches still borrow like any other query
+    let _query = world.query::<(&mut f32,)>().unwrap();
+    assert!(state.query(&world).is_err());
+}
+
+#[test]
+#[should_panic(expected = "QueryState used with a different world")]
+fn query_state_is_bound_to_one_world() {
+    let mut state = QueryState::<(&u64,)>::new();
+    assert_eq!(state.query(&query_world()).unwrap().count(), 150);
+    let _ = state.query(&World::new());
+}
+
+#[test]
+fn query_par_for_each() {
+    let mut world = query_world();
+    world.add_table(Table::from_fn(10_000, |idx| (idx as u64, 1.0f32)));
+    let visited = std::sync::atomic::AtomicUsize::new(0);
+    let mut query = world.query::<(&mut u64, &f32)>().unwrap();
+    query.par_for_each(64, |(value, float)| {
+        *value += *float as u64;
+        visited.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    });
+    assert_eq!(visited.into_inner(), 10_100);
+    drop(query);
+
+    let mut query = world.query::<(&u64,)>().unwrap();
+    assert_eq!(query.iter().map(|(value,)| *value).sum::<u64>(), 2 * (0..100).sum::<u64>() + (0..50).sum::<u64>() + (1..=10_000).sum::<u64>());
+}
+
+#[test]
+fn query_combinations() {
+    let world = query_world();
+    let query = world.query::<(&u64,)>().unwrap();
+    assert_eq!(query.iter_combinations::<2>().count(), 150 * 149 / 2);
+    assert!(query.iter_combinations::<2>().all(|[(a,), (b,)]| !std::ptr::eq(a, b)));
+    assert_eq!(query.iter_combinations::<3>().count(), 150 * 149 * 148 / 6);
+    assert_eq!(query.iter_combinations::<151>().count(), 0);
+    drop(query);
+
+    // every row is paired with every other one, across tables
+    let mut query = world.query::<(&mut u64,)>().unwrap();
+    let mut combinations = query.iter_combinations_mut::<2>();
+    while let Some([(a,), (b,)]) = combinations.fetch_next() {
+        *a += 1;
+        *b += 1;
+    }
+    drop(query);
+    let mut query = world.query::<(&u64, &f32)>().unwrap();
+    assert!(query.iter().all(|(value, float)| *value == *float as u64 + 149));
+}
+
+#[test]
+fn query_get_by_entity() {
+    let mut world = query_world();
+    let first = world.row_entities(0)[0];
+    let enemy = world.row_entities(1)[7];
+    let player = world.spawn_in(1, (1000u64, 3u8, Enemy));
+    assert_eq!(world.entities().len(), 161);
+
+    let query = world.query::<(&u64, &u8)>().unwrap();
+    assert_eq!(query.get(enemy).unwrap(), (&7, &7));
+    assert_eq!(query.get(player).unwrap(), (&1000, &3));
+    assert_eq!(query.get(first).err(), Some(QueryEntityError::QueryDoesNotMatch(first)));
+    drop(query);
+
+    // the last row moves into the despawned one
+    assert!(world.despawn(enemy));
+    assert!(!world.despawn(enemy));
+    let mut query = world.query::<(&mut u64,)>().unwrap();
+    assert_eq!(query.get_mut(enemy).err(), Some(QueryEntityError::NoSuchEntity(enemy)));
+    *query.get_mut(player).unwrap().0 += 1;
+    let [(a,), (b,)] = query.get_many_mut([first, player]).unwrap();
+    std::mem::swap(a, b);
+    assert_eq!(query.get_many_mut([player, first, player]).err(), Some(QueryEntityError::AliasedMutability(player)));
+    drop(query);
+
+    let query = world.query::<(&u64, &u8)>().unwrap();
+    assert_eq!(query.get(player).unwrap(), (&0, &3));
+    assert_eq!(world.entities().location(player).unwrap().row, 7);
+
+    // the freed index is reused with a new generation
+    drop(query);
+    let reused = world.spawn_in(0, (5u64, 5.0f32));
+    assert_eq!((reused.index(), reused.generation()), (enemy.index(), enemy.generation() + 1));
+    assert_eq!(world.query::<(&u64,)>().unwrap().get(reused).unwrap(), (&5,));
+}
+
+#[test]
+fn query_single() {
+    let mut world = query_world();
+    let mut query = world.query::<(&mut u64, &f32)>().unwrap();
+    assert_eq!(query.get_single().err(), Some(QuerySingleError::MultipleEntities));
+    drop(query);
+    assert_eq!(world.query::<(&u8, &f32)>().unwrap().get_single().err(), Some(QuerySingleError::NoEntities));
+
+    let player = world.add_table(Table::from_fn(1, |_| (1u8, String::from("player"))));
+    let mut query = world.query::<(&mut String,)>().unwrap();
+    query.single().0.push('!');
+    assert_eq!(query.get_mut(world.row_entities(player)[0]).unwrap().0, "player!");
+}
+
+struct Turret {
+    aim: Option<f32>,
+}
+
+struct Target(Entity);
+
+impl Relation for Target {
+    fn target(&self) -> Entity {
+        self.0
+    }
+}
+
+#[test]
+fn join_through_relation() {
+    let mut world = query_world();
+    let targets = world.row_entities(0)[..3].to_vec();
+    let turrets = world.add_table(Table::new_for_bundle::<(Turret, Target)>());
+    for &target in &targets {
+        world.spawn_in(turrets, (Turret { aim: None }, Target(target)));
+    }
+    world.spawn_in(turrets, (Turret { aim: None }, Target(world.row_entities(2)[0])));
+    world.despawn(targets[2]);
+
+    let mut join = world.join::<(&mut Turret,), Target, (&f32,)>().unwrap();
+    assert_eq!(join.count(), 4);
+    join.for_each(|(turret,), target| turret.aim = target.map(|(float,)| *float));
+    drop(join);
+    let mut query = world.query::<(&Turret,)>().unwrap();
+    // the third target was despawned, the last one has no u64
+    assert_eq!(query.iter().map(|(turret,)| turret.aim).collect::<Vec<_>>(), [Some(0.0), Some(1.0), None, Some(0.0)]);
+    drop(query);
+
+    // targets are borrowed against the outer query
+    assert_eq!(world.join::<(&Turret,), Target, (&mut Turret,)>().err(), Some(AccessError::AlreadyBorrowed(TypeAccess::mut_for::<Turret>())));
+    let mut join = world.join::<(&Turret,), Target, (&mut u64, &f32)>().unwrap();
+    join.for_each(|_, target| if let Some((value, _)) = target { *value += 10 });
+    drop(join);
+
+    let mut join = world.join::<(&Turret,), Target, (&u64,)>().unwrap();
+    assert_eq!(join.iter().map(|(_, target)| target.map(|(value,)| *value)).collect::<Vec<_>>(), [Some(10), Some(11), None, None]);
+}
+
+#[test]
+fn removed_components() {
+    let mut world = query_world();
+    let mut table = Table::with_storage_for_bundle::<(u64, u8)>(StorageMode::PerColumn);
+    table.extend((0..4).map(|idx| (idx as u64, idx as u8)));
+    let per_column = world.add_table(table);
+    let (first, enemy) = (world.row_entities(0)[0], world.row_entities(1)[3]);
+    let mut removed_u8 = RemovedComponents::<u8>::new();
+    let mut removed_enemies = RemovedComponents::<Enemy>::new();
+    assert_eq!(removed_u8.read(&world).count(), 0);
+
+    world.despawn(first);
+    world.despawn(enemy);
+    assert_eq!(removed_u8.read(&world).collect::<Vec<_>>(), [enemy]);
+    assert_eq!(removed_u8.read(&world).count(), 0);
+
+    // rows and columns taken out through table_mut are logged too
+    let last = *world.row_entities(per_column).last().unwrap();
+    world.table_mut(per_column).pop::<(u64, u8)>();
+    assert!(!world.entities().contains(last));
+    let remaining = world.row_entities(per_column).to_vec();
+    world.table_mut(per_column).remove_column::<u8>();
+    assert_eq!(removed_u8.read(&world).collect::<Vec<_>>(), [&[last][..], &remaining].concat());
+
+    // readers have their own cursor, and skip what was cleared before they read it
+    let mut late = RemovedComponents::<u64>::new();
+    world.clear_removed();
+    world.despawn(remaining[0]);
+    assert_eq!(late.read(&world).collect::<Vec<_>>(), [remaining[0]]);
+    assert_eq!(removed_enemies.read(&world).collect::<Vec<_>>(), []);
+    assert_eq!(removed_u8.read(&world).count(), 0);
+}
+
+#[test]
+fn removed_components_from_middle_rows() {
+    let mut world = World::new();
+    let idx = world.add_table(Table::from_fn(6, |idx| (idx as u64, idx as u8)));
+    let rows = world.row_entities(idx).to_vec();
+    let mut removed = RemovedComponents::<u64>::new();
+    let targets = Rc::new(RefCell::new(Vec::new()));
+    let seen = targets.clone();
+    world.on_remove::<u8>(move |world, trigger| {
+        assert!(!world.entities().contains(trigger.target().unwrap()));
+        seen.borrow_mut().push(trigger.target().unwrap());
+    });
+
+    // the removed rows' entities are logged, not the ones that moved into their place
+    world.table_mut(idx).swap_remove(1);
+    world.table_mut(idx).erase(2, 2);
+    assert_eq!(removed.read(&world).collect::<Vec<_>>(), [rows[1], rows[2], rows[3]]);
+    assert_eq!(*targets.borrow(), [rows[1], rows[2], rows[3]]);
+    assert_eq!(world.row_entities(idx), [rows[0], rows[5], rows[4]]);
+    let query = world.query::<(&u64, &u8)>().unwrap();
+    assert!(world.row_entities(idx).iter().all(|&entity| query.get(entity).unwrap().0 == &(entity.index() as u64)));
+}
+
+#[test]
+#[should_panic(expected = "RemovedComponents used with a different world")]
+fn removed_components_with_another_world() {
+    let mut removed = RemovedComponents::<u64>::new();
+    removed.read(&World::new()).count();
+    removed.read(&World::new()).count();
+}
+
+struct Damage(u32);
+
+#[test]
+fn observers_bubble_through_parents() {
+    let mut world = World::new();
+    let roots = world.add_table(Table::new_for_bundle::<(u32,)>());
+    let children = world.add_table(Table::new_for_bundle::<(u32, Parent)>());
+    let root = world.spawn_in(roots, (100u32,));
+    let child = world.spawn_in(children, (10u32, Parent(root)));
+    let grandchild = world.spawn_in(children, (1u32, Parent(child)));
+
+    let seen = Rc::new(Cell::new(0));
+    let hops = seen.clone();
+    world.observe(move |_, _: &mut Trigger<Damage>| hops.set(hops.get() + 1));
+    world.observe_entity(grandchild, |_, trigger: &mut Trigger<Damage>| trigger.propagate(true));
+    world.observe_entity(child, |world, trigger: &mut Trigger<Damage>| {
+        let mut query = world.query::<(&mut u32,)>().unwrap();
+        *query.get_mut(trigger.target().unwrap()).unwrap().0 -= trigger.event().0;
+        trigger.event_mut().0 *= 2;
+        trigger.propagate(true);
+    });
+
+    // stops at the root, nothing there asks to go on
+    assert_eq!(world.trigger_for(grandchild, Damage(3)).0, 6);
+    assert_eq!(seen.get(), 3);
+    assert_eq!(world.query::<(&u32,)>().unwrap().get(child).unwrap(), (&7,));
+    assert_eq!(world.trigger_for(root, Damage(3)).0, 3);
+    assert_eq!(world.trigger(Damage(3)).0, 3);
+    assert_eq!(seen.get(), 5);
+}
+
+#[test]
+fn observers_on_add_and_remove() {
+    let mut world = query_world();
+    let added = Rc::new(Cell::new(0));
+    let count = added.clone();
+    world.on_add::<u8>(move |_, _| count.set(count.get() + 1));
+    // despawning an enemy takes its partner down with it
+    let mut partners: Vec<_> = world.row_entities(0)[..50].to_vec();
+    world.on_remove::<Enemy>(move |world, trigger| {
+        assert!(!world.entities().contains(trigger.target().unwrap()));
+        world.despawn(partners.pop().unwrap());
+    });
+
+    world.spawn_in(1, (1u64, 1u8, Enemy));
+    world.add_table(Table::from_fn(3, |idx| (idx as u8, idx as f32)));
+    assert_eq!(added.get(), 4);
+
+    let enemy = world.row_entities(1)[0];
+    world.despawn(enemy);
+    assert_eq!(world.entities().len(), 160 + 4 - 2);
+    world.table_mut(1).pop::<(u64, u8, Enemy)>();
+    assert_eq!(world.row_entities(0).len(), 98);
+    assert_eq!(added.get(), 4);
+}
+
+#[test]
+fn observers_of_nested_changes_and_despawned_entities() {
+    let mut world = World::new();
+    let idx = world.add_table(Table::new_for_bundle::<(u8,)>());
+    // each spawn spawns the next one, it's observed once the outer observer returned
+    let depth = Rc::new(Cell::new(0));
+    let running = depth.clone();
+    world.on_add::<u8>(move |world, trigger| {
+        running.set(running.get() + 1);
+        assert_eq!(running.get(), 1);
+        let value = *world.query::<(&u8,)>().unwrap().get(trigger.target().unwrap()).unwrap().0;
+        if value < 3 {
+            world.spawn_in(idx, (value + 1,));
+        }
+        running.set(running.get() - 1);
+    });
+    let entity = world.spawn_in(idx, (0u8,));
+    assert_eq!(world.row_entities(idx).len(), 4);
+
+    let captured = Rc::new(());
+    let held = captured.clone();
+    world.observe_entity(entity, move |_, _: &mut Trigger<Damage>| drop(held.clone()));
+    assert_eq!(Rc::strong_count(&captured), 2);
+    world.despawn(entity);
+    assert_eq!(Rc::strong_count(&captured), 1);
+    assert_eq!(depth.get(), 0);
+}
+
+#[test]
+fn disabled_entities() {
+    let mut world = query_world();
+    let bullets: Vec<_> = world.row_entities(0)[..10].to_vec();
+    let mut state = QueryState::<(&u64, &f32)>::new();
+    let mut with_disabled = QueryState::<(&u64, &f32)>::new().including_disabled();
+    let mut removed = RemovedComponents::<Disabled>::new();
+
+    for &bullet in &bullets {
+        assert!(world.disable(bullet));
+    }
+    assert!(!world.disable(bullets[0]));
+    assert!(world.is_disabled(bullets[0]) && !world.is_disabled(world.row_entities(0)[0]));
+    assert_eq!(world.table_count(), 4);
+    assert_eq!(state.query(&world).unwrap().count(), 90);
+    assert_eq!(with_disabled.query(&world).unwrap().count(), 100);
+    assert_eq!(world.query_including_disabled::<(&u64,)>().unwrap().count(), 150);
+    assert_eq!(world.query::<(&u64,)>().unwrap().get(bullets[1]).err(), Some(QueryEntityError::QueryDoesNotMatch(bullets[1])));
+    assert_eq!(world.dynamic_query(&[TypeAccess::ref_for::<f32>()]).unwrap().count(), 100);
+    assert_eq!(world.dynamic_query_including_disabled(&[TypeAccess::ref_for::<f32>()]).unwrap().count(), 110);
+
+    // accessing Disabled asks for the disabled rows
+    let mut query = world.query::<(&mut u64, &Disabled)>().unwrap();
+    assert_eq!(query.count(), 10);
+    query.for_each(|(value, _)| *value += 1000);
+    drop(query);
+
+    // the data comes back along with the entity
+    assert!(world.enable(bullets[3]));
+    assert!(!world.enable(bullets[3]));
+    assert_eq!(world.table_count(), 4);
+    assert_eq!(state.query(&world).unwrap().get(bullets[3]).unwrap(), (&1003, &3.0));
+    assert_eq!(state.query(&world).unwrap().count(), 91);
+    assert_eq!(removed.read(&world).collect::<Vec<_>>(), [bullets[3]]);
+    assert!(world.despawn(bullets[5]));
+    assert_eq!(with_disabled.query(&world).unwrap().count(), 99);
+
+    // joins don't see disabled targets either, unless asked to
+    let turrets = world.add_table(Table::new_for_bundle::<(Turret, Target)>());
+    world.spawn_in(turrets, (Turret { aim: None }, Target(bullets[4])));
+    let mut join = world.join::<(&Turret,), Target, (&f32,)>().unwrap();
+    assert_eq!(join.iter().map(|(_, target)| target.map(|(float,)| *float)).collect::<Vec<_>>(), [None]);
+    drop(join);
+    let mut join = world.join_including_disabled::<(&Turret,), Target, (&f32,)>().unwrap();
+    assert_eq!(join.iter().map(|(_, target)| target.map(|(float,)| *float)).collect::<Vec<_>>(), [Some(4.0)]);
+}
+
+#[test]
+fn table_mut_keeps_entities() {
+    let mut world = World::new();
+    let idx = world.add_table(Table::from_fn(3, |idx| (idx as u64,)));
+    let [first, second, third] = world.row_entities(idx).to_vec().try_into().unwrap();
+
+    // the last row takes the removed one's place, along with its entity
+    world.table_mut(idx).swap_remove(0);
+    assert!(!world.entities().contains(first));
+    assert_eq!(world.query::<(&u64,)>().unwrap().get(third).unwrap(), (&2,));
+    assert_eq!(world.row_entities(idx), [third, second]);
+
+    let mut table = world.table_mut(idx);
+    let pushed = table.push((3u64,));
+    table.extend([(4u64,), (5u64,)]);
+    assert_eq!(table.insert_at(1, (6u64,)), (1u64,));
+    table.erase(1, 2);
+    let rows = world.row_entities(idx).to_vec();
+    assert_eq!(rows.len(), 3);
+    assert!(!world.entities().contains(second) && !world.entities().contains(pushed));
+    let query = world.query::<(&u64,)>().unwrap();
+    assert_eq!(rows.iter().map(|&entity| *query.get(entity).unwrap().0).collect::<Vec<_>>(), [2, 4, 5]);
+    drop(query);
+
+    assert_eq!(world.table_mut(idx).swap_pop::<(u64,)>(0), (2u64,));
+    assert_eq!(world.query::<(&u64,)>().unwrap().get(rows[2]).unwrap(), (&5,));
+    world.table_mut(idx).clear();
+    assert!(world.entities().is_empty());
+}
+
+#[test]
+fn typed_table_refs_in_declaration_order() {
+    // the columns are in name order, the references come back in the tuple's order
+    let mut sut: TypedTable<(u8, bool, u64)> = (0..10).map(|idx| (idx as u8, idx % 2 == 0, idx as u64 * 100)).collect();
+    for (byte, even, long) in sut.iter_mut() {
+        *byte += 1;
+        *even = !*even;
+        *long += 1;
+    }
+    assert!(sut.iter().enumerate().all(|(idx, row)| row == (&(idx as u8 + 1), &(idx % 2 == 1), &(idx as u64 * 100 + 1))));
+    assert_eq!(sut.pop(), (10, true, 901));
+}
+
 #[test]
 fn test_remove_if() {
 }
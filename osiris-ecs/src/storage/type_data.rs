@@ -1,28 +1,62 @@
 use std::alloc::Layout;
 use std::any::TypeId;
 use std::cmp::Ordering;
-use std::mem::MaybeUninit;
+use std::hash::{Hash, Hasher};
+use std::mem::{ManuallyDrop, MaybeUninit};
 use paste::paste;
 
 #[derive(Copy, Clone, Debug)]
 pub struct TypeMetadata {
     pub id: TypeId,
+    pub name: &'static str,
     pub layout: Layout,
     pub drop: unsafe fn(*mut u8),
+    // false if drop is a no-op and may be skipped
+    pub needs_drop: bool,
+    // only set for types registered with with_eq / with_hash, used to compare and checksum tables
+    pub eq: Option<unsafe fn(*const u8, *const u8) -> bool>,
+    pub hash: Option<unsafe fn(*const u8, &mut dyn Hasher)>,
 }
 
 impl TypeMetadata {
-    pub const unsafe fn from_raw_parts(id: TypeId, layout: Layout, drop: unsafe fn(*mut u8)) -> Self {
-        Self { id, layout, drop }
+    /// # Safety
+    /// drop must drop a value with this layout in place, and id and name must identify the type it was made for
+    pub const unsafe fn from_raw_parts(id: TypeId, name: &'static str, layout: Layout, drop: unsafe fn(*mut u8), needs_drop: bool) -> Self {
+        Self { id, name, layout, drop, needs_drop, eq: None, hash: None }
+    }
+
+    // zero sized types have no per-row storage, only a place in the table's signature
+    pub const fn is_zero_sized(&self) -> bool {
+        self.layout.size() == 0
     }
 
     pub const fn of<T: 'static + Sized>() -> Self {
         // This is very C++
         unsafe fn drop_ptr<T>(x: *mut u8) {
-            x.cast::<T>().drop_in_place()
+            unsafe { x.cast::<T>().drop_in_place() }
         }
         
-        unsafe { Self::from_raw_parts(TypeId::of::<T>(), Layout::new::<T>(), drop_ptr::<T>) }
+        unsafe { Self::from_raw_parts(TypeId::of::<T>(), std::any::type_name::<T>(), Layout::new::<T>(), drop_ptr::<T>, std::mem::needs_drop::<T>()) }
+    }
+
+    // NB: Not filled in by of::<T>(), so comparable types opt in, e.g. TypeMetadata::of::<T>().with_eq::<T>(),
+    // or #[bundle(eq, hash)] on a derived bundle
+    pub const fn with_eq<T: 'static + PartialEq>(self) -> Self {
+        unsafe fn eq_ptr<T: PartialEq>(a: *const u8, b: *const u8) -> bool {
+            unsafe { *a.cast::<T>() == *b.cast::<T>() }
+        }
+
+        assert!(self.id == TypeId::of::<T>(), "eq must be for the metadata's own type");
+        Self { eq: Some(eq_ptr::<T>), ..self }
+    }
+
+    pub const fn with_hash<T: 'static + Hash>(self) -> Self {
+        unsafe fn hash_ptr<T: Hash>(x: *const u8, mut state: &mut dyn Hasher) {
+            unsafe { (*x.cast::<T>()).hash(&mut state) }
+        }
+
+        assert!(self.id == TypeId::of::<T>(), "hash must be for the metadata's own type");
+        Self { hash: Some(hash_ptr::<T>), ..self }
     }
 }
 
@@ -37,61 +71,384 @@ impl Eq for TypeMetadata {
 
 impl PartialOrd for TypeMetadata {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.id.partial_cmp(&other.id)
+        Some(self.cmp(other))
     }
 }
 
+// By name first, the order bundles are sorted in at compile time. Ties (e.g. types from different crate versions)
+// are broken by id, which has no const ordering
 impl Ord for TypeMetadata {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.id.cmp(&other.id)
+        self.name.cmp(other.name).then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+// str comparison byte by byte, as Ord for str isn't const
+const fn name_less_than(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut i = 0;
+    while i < a.len() && i < b.len() {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+        i += 1;
+    }
+    a.len() < b.len()
+}
+
+// Rejects (at compile time when used in a const context) any list of metadata containing the same type twice
+const fn assert_unique_types(metadata: &[TypeMetadata]) {
+    let mut i = 0;
+    while i < metadata.len() {
+        let mut j = i + 1;
+        while j < metadata.len() {
+            assert!(metadata[i].id != metadata[j].id, "All item types in a bundle must be unique!");
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+// Bundles are made of parts whose lengths are only known once they're monomorphized, so their metadata is
+// flattened into a buffer of this many types
+const MAX_BUNDLE_TYPES: usize = 64;
+
+// The metadata of a bundle's parts flattened and sorted by name at compile time, so tables can match a bundle to
+// their columns in one pass. Parts still put and take their types in declaration order, sorted_index maps between
+pub struct BundleLayout {
+    metadata: [TypeMetadata; MAX_BUNDLE_TYPES],
+    // the sorted index of each type in declaration order, and the other way around
+    sorted: [usize; MAX_BUNDLE_TYPES],
+    declared: [usize; MAX_BUNDLE_TYPES],
+    len: usize,
+}
+
+impl BundleLayout {
+    pub const fn new(parts: &[&[TypeMetadata]]) -> Self {
+        let mut flat = [TypeMetadata::of::<()>(); MAX_BUNDLE_TYPES];
+        let mut len = 0;
+        let mut i = 0;
+        while i < parts.len() {
+            let mut j = 0;
+            while j < parts[i].len() {
+                assert!(len < MAX_BUNDLE_TYPES, "Bundles can't have more than MAX_BUNDLE_TYPES types!");
+                flat[len] = parts[i][j];
+                len += 1;
+                j += 1;
+            }
+            i += 1;
+        }
+        assert_unique_types(flat.split_at(len).0);
+
+        // stable insertion sort of the declaration indices, so types with the same name keep their order
+        let mut declared = [0; MAX_BUNDLE_TYPES];
+        let mut i = 0;
+        while i < len {
+            let mut j = i;
+            while j > 0 && name_less_than(flat[i].name, flat[declared[j - 1]].name) {
+                declared[j] = declared[j - 1];
+                j -= 1;
+            }
+            declared[j] = i;
+            i += 1;
+        }
+
+        let mut metadata = [TypeMetadata::of::<()>(); MAX_BUNDLE_TYPES];
+        let mut sorted = [0; MAX_BUNDLE_TYPES];
+        let mut i = 0;
+        while i < len {
+            metadata[i] = flat[declared[i]];
+            sorted[declared[i]] = i;
+            i += 1;
+        }
+        Self { metadata, sorted, declared, len }
+    }
+
+    pub const fn metadata(&self) -> &[TypeMetadata] {
+        self.metadata.split_at(self.len).0
+    }
+
+    // where the type declared at idx is in metadata
+    pub const fn sorted_index(&self, idx: usize) -> usize {
+        self.sorted[idx]
+    }
+
+    // which type in declaration order is at idx in metadata
+    pub const fn declared_index(&self, idx: usize) -> usize {
+        self.declared[idx]
+    }
+
+    // The types of this layout that are also in parts, e.g. a bundle's REQUIRED from the required types of its parts
+    pub const fn retain(&self, parts: &[&[TypeMetadata]]) -> Self {
+        let mut kept = [TypeMetadata::of::<()>(); MAX_BUNDLE_TYPES];
+        let mut len = 0;
+        let mut i = 0;
+        while i < self.len {
+            let mut j = 0;
+            while j < parts.len() {
+                let mut k = 0;
+                while k < parts[j].len() {
+                    if parts[j][k].id == self.metadata[i].id {
+                        kept[len] = self.metadata[i];
+                        len += 1;
+                    }
+                    k += 1;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        // already sorted, so this only fills in the indices
+        Self::new(&[kept.split_at(len).0])
+    }
+}
+
+// Where each part starts in the flattened list of types, given how many types each part has
+const fn part_starts<const N: usize>(lens: [usize; N]) -> [usize; N] {
+    let mut starts = [0; N];
+    let mut i = 1;
+    while i < N {
+        starts[i] = starts[i - 1] + lens[i - 1];
+        i += 1;
     }
+    starts
 }
 
-// A bundle represents something that can be put into a table
+/// A bundle represents something that can be put into a table
+///
+/// # Safety
+/// `METADATA` must describe exactly the types `put` hands over and `take` reads back, none of them twice. `put` must
+/// pass f each type of the instance once, as an initialized value it no longer uses or drops, and `take` may only
+/// assume a type was written if f returned true for it
 pub unsafe trait DynamicBundle {
-    // iterator of the ids contained within this bundle
-    fn type_metadata() -> impl IntoIterator<Item=TypeMetadata>;
-    unsafe fn put(self, f: impl FnMut(*mut u8, TypeId));
-    unsafe fn take(f: impl FnMut(*mut u8, TypeId)) -> Self;
+    // metadata of every type this bundle may contain, sorted by name (see BundleLayout) so tables can match it to
+    // their columns without searching. Unsorted metadata still works, it's just slower
+    const METADATA: &'static [TypeMetadata];
+    // the subset of METADATA every instance contains, the rest are only present on some instances (e.g. Option fields)
+    const REQUIRED: &'static [TypeMetadata] = Self::METADATA;
+
+    // metadata of the types this particular instance will put, in any order
+    fn instance_metadata(&self, f: impl FnMut(TypeMetadata)) {
+        Self::METADATA.iter().copied().for_each(f)
+    }
+
+    /// f is called once per type in instance_metadata, with the type's index in METADATA
+    ///
+    /// # Safety
+    /// f must copy each value out of its pointer and take ownership of it
+    unsafe fn put(self, f: impl FnMut(*mut u8, usize));
+
+    /// f is called with the index in METADATA of each type, and must write the type to the pointer and return true,
+    /// or return false if it has no such type
+    ///
+    /// # Safety
+    /// f must return true for every type in REQUIRED, and only after writing an initialized value of that type
+    unsafe fn take(f: impl FnMut(*mut u8, usize) -> bool) -> Self;
+}
+
+/// Bundles that always contain every type, so a row can be borrowed as a tuple of references
+///
+/// # Safety
+/// `Ref` and `Mut` may only borrow the types in `METADATA`, each exactly once, and `refs`/`refs_mut` must cast the
+/// n-th pointer they're given to the type of `METADATA[n]` and nothing else
+pub unsafe trait BundleRefs: DynamicBundle {
+    type Ref<'a>;
+    type Mut<'a>;
+
+    /// # Safety
+    /// ptrs must yield one pointer per type in METADATA order, each to an initialized value of that type which stays
+    /// valid and isn't mutated for 'a
+    unsafe fn refs<'a>(ptrs: impl Iterator<Item = *mut u8>) -> Self::Ref<'a>;
+
+    /// # Safety
+    /// As refs, and no other reference to any of the values may exist for 'a
+    unsafe fn refs_mut<'a>(ptrs: impl Iterator<Item = *mut u8>) -> Self::Mut<'a>;
+}
+
+/// A column of values to import in bulk, owned values are moved and borrowed ones copied
+///
+/// # Safety
+/// `put` must call f exactly once, with a pointer to `len` initialized items that stays valid for the call, and must
+/// neither use nor drop those items afterwards
+pub unsafe trait Column {
+    type Item: 'static;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// # Safety
+    /// f must copy all len items out of the pointer and take ownership of them, they're forgotten afterwards
+    unsafe fn put(self, f: impl FnOnce(*const u8));
+}
+
+unsafe impl<T: 'static> Column for Vec<T> {
+    type Item = T;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    unsafe fn put(mut self, f: impl FnOnce(*const u8)) {
+        // the vec only frees its buffer once the values have been moved out
+        unsafe { self.set_len(0) };
+        f(self.as_ptr().cast());
+    }
+}
+
+unsafe impl<T: 'static + Copy> Column for &[T] {
+    type Item = T;
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    unsafe fn put(self, f: impl FnOnce(*const u8)) {
+        f(self.as_ptr().cast());
+    }
+}
+
+/// One Column per type, e.g. (Vec<A>, &[B]) imports rows of (A, B)
+///
+/// # Safety
+/// Every type in `Bundle::METADATA` must have exactly one column and nothing else may. `put` must call f once per
+/// column, with the column's type id and a pointer to as many initialized items as column_lens reported for it,
+/// and must neither use nor drop those items afterwards
+pub unsafe trait ColumnBundle {
+    // the bundle each row would be
+    type Bundle: DynamicBundle;
+
+    // f is called with the length of each column, in order
+    fn column_lens(&self, f: impl FnMut(usize));
+
+    /// # Safety
+    /// The columns must all be the same length, and f must copy every item out of each pointer and take ownership
+    /// of them
+    unsafe fn put(self, f: impl FnMut(*const u8, TypeId));
+}
+
+// Marks a tuple of bundles whose types are flattened into a single bundle, e.g. Nested(((A, B), C)) puts the same row
+// as (A, B, C). Plain tuples never look inside their elements, each element is one component
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Nested<B>(pub B);
+
+// The layout of a tuple or derived bundle, shared by its METADATA, REQUIRED, put and take
+#[doc(hidden)]
+pub trait HasBundleLayout {
+    const LAYOUT: &'static BundleLayout;
+}
+
+// Reorders a row's pointers from METADATA order back to declaration order
+fn declared_ptrs<const N: usize>(layout: &BundleLayout, ptrs: impl Iterator<Item = *mut u8>) -> [*mut u8; N] {
+    let mut declared = [std::ptr::null_mut(); N];
+    for (idx, ptr) in ptrs.enumerate() {
+        declared[layout.declared_index(idx)] = ptr;
+    }
+    declared
 }
 
 macro_rules! tuple_bundle_impl {
     ($($tuple_types:ty),*) => {
         paste! {
+            impl <$($tuple_types: 'static + Sized),*> HasBundleLayout for ($($tuple_types,)*) {
+                const LAYOUT: &'static BundleLayout = &BundleLayout::new(&[$(&[TypeMetadata::of::<$tuple_types>()]),*]);
+            }
+
             unsafe impl <$($tuple_types: 'static + Sized),*> DynamicBundle for ($($tuple_types,)*)
             where ($($tuple_types,)*): Sized {
-                fn type_metadata() -> impl IntoIterator<Item=TypeMetadata> {
-                    let mut x = [$(TypeMetadata::of::<$tuple_types>()),*];
-                    x.sort_unstable();
-                    x
-                }
+                const METADATA: &'static [TypeMetadata] = <Self as HasBundleLayout>::LAYOUT.metadata();
 
-                unsafe fn put(mut self, mut f: impl FnMut(*mut u8, TypeId)) {
-                    let ($([< raw_ $tuple_types:snake >],)*) = &mut self;
-                    let mut x = [$((([< raw_ $tuple_types:snake >] as *mut $tuple_types).cast::<u8>(), TypeId::of::<$tuple_types>())),*];
-                    x.sort_unstable_by_key(|(_,id)| *id);
-                    for (a, b) in x.into_iter() {
-                        f(a, b);
+                unsafe fn put(self, mut f: impl FnMut(*mut u8, usize)) {
+                    let layout = <Self as HasBundleLayout>::LAYOUT;
+                    // the table owns the values once they've been copied out
+                    let mut values = ManuallyDrop::new(self);
+                    let ($([< part_ $tuple_types:snake >],)*) = &mut *values;
+                    let ptrs = [$((&raw mut *[< part_ $tuple_types:snake >]).cast::<u8>()),*];
+                    for (idx, ptr) in ptrs.into_iter().enumerate() {
+                        f(ptr, layout.sorted_index(idx));
                     }
-                    std::mem::forget(self);
                 }
 
-                unsafe fn take(mut f: impl FnMut(*mut u8, TypeId)) -> Self {
-                    let mut raw = ($(MaybeUninit::<$tuple_types>::uninit(),)*);
-                    let ($([< raw_ $tuple_types:snake >],)*) = &mut raw;
-                    let mut refs = [
-                        $(
-                        {
-                            ([< raw_ $tuple_types:snake >].as_mut_ptr().cast(), TypeId::of::<$tuple_types>())
-                        }
-                        ),*
-                    ];
-                    refs.sort_unstable_by_key(|(_,id)| *id);
-                    for (a, b) in refs.into_iter() {
-                        f(a, b);
+                unsafe fn take(mut f: impl FnMut(*mut u8, usize) -> bool) -> Self {
+                    let layout = <Self as HasBundleLayout>::LAYOUT;
+                    let mut values = ($(MaybeUninit::<$tuple_types>::uninit(),)*);
+                    let ($([< part_ $tuple_types:snake >],)*) = &mut values;
+                    let ptrs = [$([< part_ $tuple_types:snake >].as_mut_ptr().cast::<u8>()),*];
+                    for (idx, ptr) in ptrs.into_iter().enumerate() {
+                        assert!(f(ptr, layout.sorted_index(idx)), "All types in a tuple bundle are required");
                     }
-                    // NB: Since raw is maybe uninit we don't need to drop it
-                    unsafe { std::mem::transmute_copy::<_,Self>(&raw) }
+                    let ($([< part_ $tuple_types:snake >],)*) = values;
+                    unsafe { ($([< part_ $tuple_types:snake >].assume_init(),)*) }
+                }
+            }
+
+            unsafe impl <$($tuple_types: 'static + Sized),*> BundleRefs for ($($tuple_types,)*)
+            where ($($tuple_types,)*): Sized {
+                type Ref<'a> = ($(&'a $tuple_types,)*);
+                type Mut<'a> = ($(&'a mut $tuple_types,)*);
+
+                unsafe fn refs<'a>(ptrs: impl Iterator<Item = *mut u8>) -> Self::Ref<'a> {
+                    let [$([< ptr_ $tuple_types:snake >]),*] = declared_ptrs(<Self as HasBundleLayout>::LAYOUT, ptrs);
+                    unsafe { ($(&*[< ptr_ $tuple_types:snake >].cast::<$tuple_types>(),)*) }
+                }
+
+                unsafe fn refs_mut<'a>(ptrs: impl Iterator<Item = *mut u8>) -> Self::Mut<'a> {
+                    let [$([< ptr_ $tuple_types:snake >]),*] = declared_ptrs(<Self as HasBundleLayout>::LAYOUT, ptrs);
+                    unsafe { ($(&mut *[< ptr_ $tuple_types:snake >].cast::<$tuple_types>(),)*) }
+                }
+            }
+
+            impl <$($tuple_types: DynamicBundle),*> HasBundleLayout for Nested<($($tuple_types,)*)> {
+                const LAYOUT: &'static BundleLayout = &BundleLayout::new(&[$(<$tuple_types as DynamicBundle>::METADATA),*]);
+            }
+
+            unsafe impl <$($tuple_types: DynamicBundle),*> DynamicBundle for Nested<($($tuple_types,)*)> {
+                const METADATA: &'static [TypeMetadata] = <Self as HasBundleLayout>::LAYOUT.metadata();
+                const REQUIRED: &'static [TypeMetadata] = {
+                    let required: &'static BundleLayout = &<Self as HasBundleLayout>::LAYOUT.retain(&[$(<$tuple_types as DynamicBundle>::REQUIRED),*]);
+                    required.metadata()
+                };
+
+                fn instance_metadata(&self, mut f: impl FnMut(TypeMetadata)) {
+                    let Nested(($([< part_ $tuple_types:snake >],)*)) = self;
+                    $(
+                    [< part_ $tuple_types:snake >].instance_metadata(&mut f);
+                    )*
+                }
+
+                unsafe fn put(self, mut f: impl FnMut(*mut u8, usize)) {
+                    let layout = <Self as HasBundleLayout>::LAYOUT;
+                    let Nested(($([< part_ $tuple_types:snake >],)*)) = self;
+                    let [$([< start_ $tuple_types:snake >]),*] = part_starts([$(<$tuple_types as DynamicBundle>::METADATA.len()),*]);
+                    $(
+                    unsafe { [< part_ $tuple_types:snake >].put(|ptr, idx| f(ptr, layout.sorted_index([< start_ $tuple_types:snake >] + idx))) };
+                    )*
+                }
+
+                unsafe fn take(mut f: impl FnMut(*mut u8, usize) -> bool) -> Self {
+                    let layout = <Self as HasBundleLayout>::LAYOUT;
+                    let [$([< start_ $tuple_types:snake >]),*] = part_starts([$(<$tuple_types as DynamicBundle>::METADATA.len()),*]);
+                    Nested(unsafe { ($(<$tuple_types as DynamicBundle>::take(|ptr, idx| f(ptr, layout.sorted_index([< start_ $tuple_types:snake >] + idx))),)*) })
+                }
+            }
+
+            unsafe impl <$($tuple_types: Column),*> ColumnBundle for ($($tuple_types,)*) {
+                type Bundle = ($(<$tuple_types as Column>::Item,)*);
+
+                fn column_lens(&self, mut f: impl FnMut(usize)) {
+                    let ($([< column_ $tuple_types:snake >],)*) = self;
+                    $(
+                    f([< column_ $tuple_types:snake >].len());
+                    )*
+                }
+
+                unsafe fn put(self, mut f: impl FnMut(*const u8, TypeId)) {
+                    let ($([< column_ $tuple_types:snake >],)*) = self;
+                    $(
+                    unsafe { [< column_ $tuple_types:snake >].put(|ptr| f(ptr, TypeId::of::<<$tuple_types as Column>::Item>())) };
+                    )*
                 }
             }
         }
@@ -109,3 +466,12 @@ macro_rules! all_tuple_impl_for {
 }
 
 all_tuple_impl_for!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z);
+
+// The empty bundle, e.g. for moving a row without adding anything
+unsafe impl DynamicBundle for () {
+    const METADATA: &'static [TypeMetadata] = &[];
+
+    unsafe fn put(self, _f: impl FnMut(*mut u8, usize)) {}
+
+    unsafe fn take(_f: impl FnMut(*mut u8, usize) -> bool) -> Self {}
+}
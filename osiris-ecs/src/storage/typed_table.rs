@@ -0,0 +1,164 @@
+use crate::storage::type_data::{BundleRefs, DynamicBundle};
+use crate::storage::{BundleError, IntoIter, StorageOptions, Table};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+// A Table known to hold B. Compatibility is checked once when it's made, so row operations skip the per call checks
+pub struct TypedTable<B: DynamicBundle> {
+    table: Table,
+    // where B's types are in the table, its columns can't change while it's typed
+    positions: Vec<Option<usize>>,
+    _marker: PhantomData<fn() -> B>,
+}
+
+impl<B: DynamicBundle> TypedTable<B> {
+    // bundles with optional types still have to match the table exactly, one instance at a time
+    const HAS_OPTIONAL: bool = B::REQUIRED.len() != B::METADATA.len();
+
+    pub fn new() -> Self {
+        Self::with_storage(StorageOptions::default())
+    }
+
+    pub fn with_storage(options: impl Into<StorageOptions>) -> Self {
+        Self::from_table_unchecked(Table::with_storage_for_bundle::<B>(options))
+    }
+
+    // Hands the table back if B can't be taken from it
+    #[allow(clippy::result_large_err)]
+    pub fn from_table(table: Table) -> Result<Self, (Table, BundleError)> {
+        match table.check_bundle::<B>() {
+            Ok(()) => Ok(Self::from_table_unchecked(table)),
+            Err(err) => Err((table, err)),
+        }
+    }
+
+    fn from_table_unchecked(table: Table) -> Self {
+        Self { positions: table.bundle_positions::<B>(), table, _marker: PhantomData }
+    }
+
+    pub fn into_table(self) -> Table {
+        self.table
+    }
+
+    pub fn reserve(&mut self, capacity: usize) {
+        self.table.reserve(capacity);
+    }
+
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    pub fn push(&mut self, data: B) {
+        if Self::HAS_OPTIONAL {
+            return self.table.push(data);
+        }
+        self.table.reserve(self.table.len + 1);
+        unsafe { self.table.put_column_at(self.table.len, data, &self.positions) }
+        self.table.len += 1;
+    }
+
+    pub fn insert_at(&mut self, idx: usize, data: B) -> B {
+        assert!(idx < self.table.len);
+        if Self::HAS_OPTIONAL {
+            self.table.check_instance(&data).unwrap_or_else(|err| panic!("{err}"));
+        }
+        unsafe {
+            let output = self.table.take_column_at(idx, &self.positions);
+            self.table.put_column_at(idx, data, &self.positions);
+            output
+        }
+    }
+
+    pub fn swap_pop(&mut self, idx: usize) -> B {
+        assert!(idx < self.table.len);
+        unsafe { self.table.buf.swap_columns(idx, self.table.len - 1) };
+        self.pop()
+    }
+
+    pub fn swap_remove(&mut self, idx: usize) {
+        self.table.swap_remove(idx);
+    }
+
+    pub fn pop(&mut self) -> B {
+        assert!(self.table.len > 0);
+        self.table.len -= 1;
+        unsafe { self.table.take_column_at(self.table.len, &self.positions) }
+    }
+
+    pub fn get_cloned(&self, idx: usize) -> B where B: Clone {
+        self.table.get_cloned(idx)
+    }
+
+    pub fn extend(&mut self, iter: impl IntoIterator<Item = B>) {
+        let mut iter = iter.into_iter();
+        if !Self::HAS_OPTIONAL {
+            self.table.reserve(self.table.len + iter.size_hint().0);
+            unsafe { self.table.put_column_from_iter_at(iter.by_ref(), &self.positions) }
+        }
+        for remaining_item in iter {
+            self.push(remaining_item);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = B::Ref<'_>> where B: BundleRefs {
+        self.column_ptrs().flat_map(|(columns, len)| (0..len).map(move |idx| unsafe {
+            B::refs(columns.iter().map(|&(ptr, stride)| ptr.add(stride * idx)))
+        }))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = B::Mut<'_>> where B: BundleRefs {
+        // every row is yielded once, so the references never alias
+        self.column_ptrs().flat_map(|(columns, len)| (0..len).map(move |idx| unsafe {
+            B::refs_mut(columns.iter().map(|&(ptr, stride)| ptr.add(stride * idx)))
+        }))
+    }
+
+    fn column_ptrs(&self) -> impl Iterator<Item = (Vec<(*mut u8, usize)>, usize)> {
+        let positions: Vec<_> = self.positions.iter().map(|position| position.expect("Borrowed bundles have every type")).collect();
+        self.table.column_blocks_at(positions)
+    }
+}
+
+impl<B: DynamicBundle> Default for TypedTable<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Only shared access, changing the columns through the erased table could break B's invariant
+impl<B: DynamicBundle> Deref for TypedTable<B> {
+    type Target = Table;
+
+    fn deref(&self) -> &Table {
+        &self.table
+    }
+}
+
+impl<B: DynamicBundle> From<TypedTable<B>> for Table {
+    fn from(table: TypedTable<B>) -> Self {
+        table.into_table()
+    }
+}
+
+impl<B: DynamicBundle> IntoIterator for TypedTable<B> {
+    type Item = B;
+    type IntoIter = IntoIter<B>;
+
+    fn into_iter(self) -> IntoIter<B> {
+        unsafe { IntoIter::new_unchecked(self.table) }
+    }
+}
+
+impl<B: DynamicBundle> FromIterator<B> for TypedTable<B> {
+    fn from_iter<I: IntoIterator<Item = B>>(iter: I) -> Self {
+        let mut init = Self::new();
+        init.extend(iter);
+        init
+    }
+}
+
+impl<B: DynamicBundle> Extend<B> for TypedTable<B> {
+    fn extend<I: IntoIterator<Item = B>>(&mut self, iter: I) {
+        TypedTable::extend(self, iter)
+    }
+}
@@ -0,0 +1,498 @@
+use crate::entity::{Disabled, Entities, Entity, EntityLocation, Relation};
+use crate::storage::{AccessError, Accessible, Borrows, CompareError, DynamicBundle, DynamicQuery, JoinQuery, Query, StableHasher, Table, TypeAccess, TypeMetadata};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Deref, Range};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+mod observer;
+
+pub use observer::{OnAdd, OnRemove, Trigger};
+use observer::Observers;
+
+// Tells worlds apart, so state kept for one world is never used with another
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WorldId(u64);
+
+impl Default for WorldId {
+    fn default() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// Owns every table, column access while queries may be alive only goes through the borrow flags
+#[derive(Default)]
+pub struct World {
+    id: WorldId,
+    tables: Vec<Table>,
+    borrows: Borrows,
+    entities: Entities,
+    // the entity of every row, per table
+    row_entities: Vec<Vec<Entity>>,
+    // a small id per column type, to build table signatures from
+    component_ids: HashMap<TypeId, usize>,
+    signatures: Vec<Signature>,
+    // every table whose set of columns was added or changed, in order. Its length is the archetype generation
+    archetype_log: Vec<usize>,
+    // the entities that lost a component, per component type
+    removed: HashMap<TypeId, RemovedLog>,
+    observers: Observers,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the index of the new table, every row it already has becomes an entity
+    pub fn add_table(&mut self, table: Table) -> usize {
+        let signature = Self::signature_of(&mut self.component_ids, &table);
+        self.tables.push(table);
+        self.row_entities.push(Vec::new());
+        self.signatures.push(signature);
+        self.archetype_log.push(self.tables.len() - 1);
+        self.spawn_rows(self.tables.len() - 1, 0);
+        self.flush_lifecycle();
+        self.tables.len() - 1
+    }
+
+    pub fn spawn_in<B: DynamicBundle>(&mut self, table: usize, data: B) -> Entity {
+        self.tables[table].push(data);
+        let entity = self.entities.alloc(EntityLocation { table, row: self.row_entities[table].len() });
+        self.row_entities[table].push(entity);
+        Self::log_added(&mut self.observers, self.tables[table].type_metadata().map(|metadata| metadata.id), &[entity]);
+        self.flush_lifecycle();
+        entity
+    }
+
+    // Drops the entity's row, false if it was already despawned
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        let Some(EntityLocation { table, row }) = self.entities.location(entity) else {
+            return false;
+        };
+        self.swap_remove_row(table, row, |table, row| table.swap_remove(row));
+        true
+    }
+
+    // Forgets every logged removal, e.g. once per frame. Readers that fell behind skip what they missed
+    pub fn clear_removed(&mut self) {
+        for log in self.removed.values_mut() {
+            log.cleared += log.entities.len();
+            log.entities.clear();
+        }
+    }
+
+    // Keeps the entity's data but takes it out of queries, by moving its row into a table with a Disabled column.
+    // False if the entity is dead or already disabled
+    pub fn disable(&mut self, entity: Entity) -> bool {
+        let Some(location) = self.entities.location(entity).filter(|location| !self.is_disabled_table(location.table)) else {
+            return false;
+        };
+        let types = self.tables[location.table].type_metadata().chain([TypeMetadata::of::<Disabled>()]).collect();
+        self.move_entity(entity, location, types, (Disabled,));
+        Self::log_added(&mut self.observers, [TypeId::of::<Disabled>()], &[entity]);
+        self.flush_lifecycle();
+        true
+    }
+
+    // False if the entity is dead or wasn't disabled
+    pub fn enable(&mut self, entity: Entity) -> bool {
+        let Some(location) = self.entities.location(entity).filter(|location| self.is_disabled_table(location.table)) else {
+            return false;
+        };
+        let types = self.tables[location.table].type_metadata().filter(|metadata| metadata.id != TypeId::of::<Disabled>()).collect();
+        self.move_entity(entity, location, types, ());
+        self.flush_lifecycle();
+        true
+    }
+
+    pub fn is_disabled(&self, entity: Entity) -> bool {
+        self.entities.location(entity).is_some_and(|location| self.is_disabled_table(location.table))
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    // the entity of every row of the table, in row order
+    pub fn row_entities(&self, table: usize) -> &[Entity] {
+        &self.row_entities[table]
+    }
+
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    // A checksum of every table and the entity of each row, e.g. for lockstep peers to detect desyncs. Every column
+    // needs a hash in its metadata, see storage::checksum
+    pub fn checksum(&self) -> Result<u64, CompareError> {
+        let mut state = StableHasher::default();
+        for (table, entities) in self.tables.iter().zip(&self.row_entities) {
+            table.try_hash(&mut state)?;
+            entities.hash(&mut state);
+        }
+        Ok(state.finish())
+    }
+
+    // Exclusive access, so no query can be alive. NB: rows pushed or removed through it are assumed to be at the end
+    // of the table, use despawn to remove an entity from anywhere else
+    pub fn table_mut(&mut self, idx: usize) -> TableMut<'_> {
+        TableMut { world: self, idx }
+    }
+
+    pub fn id(&self) -> WorldId {
+        self.id
+    }
+
+    // Bumped whenever a table is added or changes columns, cached queries only need to look at tables logged since
+    pub fn archetype_generation(&self) -> usize {
+        self.archetype_log.len()
+    }
+
+    // Matches every table on each call, see QueryState for queries that run often
+    pub fn query<Q: Accessible>(&self) -> Result<Query<'_, Q>, AccessError> {
+        Query::new(self, false)
+    }
+
+    // Like query, but disabled entities are visited too
+    pub fn query_including_disabled<Q: Accessible>(&self) -> Result<Query<'_, Q>, AccessError> {
+        Query::new(self, true)
+    }
+
+    // Q for every row with an R, joined with J for the entity R points at
+    pub fn join<Q: Accessible, R: Relation, J: Accessible>(&self) -> Result<JoinQuery<'_, Q, R, J>, AccessError> {
+        JoinQuery::new(self, false)
+    }
+
+    // Like join, but disabled entities are visited and joined with too
+    pub fn join_including_disabled<Q: Accessible, R: Relation, J: Accessible>(&self) -> Result<JoinQuery<'_, Q, R, J>, AccessError> {
+        JoinQuery::new(self, true)
+    }
+
+    pub fn dynamic_query(&self, accesses: &[TypeAccess]) -> Result<DynamicQuery<'_>, AccessError> {
+        DynamicQuery::new(self, accesses, false)
+    }
+
+    // Like dynamic_query, but disabled entities are visited too
+    pub fn dynamic_query_including_disabled(&self, accesses: &[TypeAccess]) -> Result<DynamicQuery<'_>, AccessError> {
+        DynamicQuery::new(self, accesses, true)
+    }
+
+    pub(crate) fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub(crate) fn borrows(&self) -> &Borrows {
+        &self.borrows
+    }
+
+    // the tables whose columns were added or changed from generation onwards, may repeat
+    pub(crate) fn archetype_changes(&self, generation: usize) -> &[usize] {
+        &self.archetype_log[generation..]
+    }
+
+    // None if one of the types was never part of a table, so nothing can match yet
+    pub(crate) fn signature_for(&self, ids: impl IntoIterator<Item = TypeId>) -> Option<Signature> {
+        let mut signature = Signature::default();
+        for id in ids {
+            signature.insert(*self.component_ids.get(&id)?);
+        }
+        Some(signature)
+    }
+
+    pub(crate) fn signature(&self, table: usize) -> &Signature {
+        &self.signatures[table]
+    }
+
+    fn is_disabled_table(&self, table: usize) -> bool {
+        self.tables[table].type_metadata().any(|metadata| metadata.id == TypeId::of::<Disabled>())
+    }
+
+    // Moves the entity's row to a table with exactly the given columns, made with the same storage if there's none.
+    // Columns the new table lacks are dropped and logged as removed
+    fn move_entity(&mut self, entity: Entity, from: EntityLocation, types: Vec<TypeMetadata>, extra: impl DynamicBundle) {
+        let existing = self.signature_for(types.iter().map(|metadata| metadata.id))
+            .and_then(|signature| self.signatures.iter().position(|other| *other == signature));
+        let to = match existing {
+            Some(to) => to,
+            None => self.add_table(Table::with_storage(types, self.tables[from.table].storage_options())),
+        };
+
+        let (src_len, row) = (self.tables[from.table].len(), self.tables[to].len());
+        let dropped: Vec<_> = self.tables[from.table].type_metadata().map(|metadata| metadata.id)
+            .filter(|&id| self.tables[to].type_metadata().all(|metadata| metadata.id != id))
+            .collect();
+        // the row has moved once the source lost it, even if dropping the columns the destination lacks panics
+        let guard = OnExit { world: self, f: move |world: &mut World| {
+            if world.tables[from.table].len() < src_len {
+                // the last row of the source took the moved one's place
+                world.row_entities[from.table].swap_remove(from.row);
+                if let Some(&moved) = world.row_entities[from.table].get(from.row) {
+                    world.entities.set_location(moved, from);
+                }
+                world.row_entities[to].push(entity);
+                world.entities.set_location(entity, EntityLocation { table: to, row });
+                Self::log_removed(&mut world.removed, &mut world.observers, dropped.iter().copied(), &[entity]);
+            }
+        } };
+        let tables = &mut guard.world.tables;
+        let (src, dst) = if from.table < to {
+            let (head, tail) = tables.split_at_mut(to);
+            (&mut head[from.table], &mut tail[0])
+        } else {
+            let (head, tail) = tables.split_at_mut(from.table);
+            (&mut tail[0], &mut head[to])
+        };
+        src.move_row_to(from.row, dst, extra);
+    }
+
+    // Gives every row from first on an entity
+    fn spawn_rows(&mut self, table: usize, first: usize) {
+        let World { tables, entities, row_entities, observers, .. } = self;
+        let rows = &mut row_entities[table];
+        debug_assert_eq!(rows.len(), first);
+        for row in first..tables[table].len() {
+            rows.push(entities.alloc(EntityLocation { table, row }));
+        }
+        Self::log_added(observers, tables[table].type_metadata().map(|metadata| metadata.id), &rows[first..]);
+    }
+
+    // Runs f to swap the last row into row's place and drop or take the row, then despawns the row's entity
+    fn swap_remove_row<R>(&mut self, table: usize, row: usize, f: impl FnOnce(&mut Table, usize) -> R) -> R {
+        let len = self.tables[table].len();
+        // the entity is gone once the table lost its row, even if one of the row's drops panics. A panic before
+        // that (e.g. a bad index or bundle) leaves it alone
+        let guard = OnExit { world: self, f: move |world: &mut World| {
+            if world.tables[table].len() < len {
+                let World { tables, entities, row_entities, removed, observers, .. } = world;
+                let entity = row_entities[table].swap_remove(row);
+                entities.free(entity);
+                observers.despawned(entity);
+                Self::log_removed(removed, observers, tables[table].type_metadata().map(|metadata| metadata.id), &[entity]);
+                if let Some(&moved) = row_entities[table].get(row) {
+                    entities.set_location(moved, EntityLocation { table, row });
+                }
+            }
+        } };
+        let output = f(&mut guard.world.tables[table], row);
+        drop(guard);
+        self.flush_lifecycle();
+        output
+    }
+
+    // Runs f to erase rows, then despawns their entities. The rows after them move down to rows.start
+    fn erase_rows(&mut self, table: usize, rows: Range<usize>, f: impl FnOnce(&mut Table)) {
+        let len = self.tables[table].len();
+        // as in swap_remove_row, the table closes the gap even if one of the drops panics
+        let guard = OnExit { world: self, f: move |world: &mut World| {
+            if world.tables[table].len() < len {
+                let World { tables, entities, row_entities, removed, observers, .. } = world;
+                let gone: Vec<_> = row_entities[table].drain(rows.clone()).collect();
+                for &entity in &gone {
+                    entities.free(entity);
+                    observers.despawned(entity);
+                }
+                Self::log_removed(removed, observers, tables[table].type_metadata().map(|metadata| metadata.id), &gone);
+                for (row, &entity) in row_entities[table].iter().enumerate().skip(rows.start) {
+                    entities.set_location(entity, EntityLocation { table, row });
+                }
+            }
+        } };
+        f(&mut guard.world.tables[table]);
+        drop(guard);
+        self.flush_lifecycle();
+    }
+
+    // Logs the column change of every row and tells cached queries to match the table again
+    fn columns_changed(&mut self, table: usize, removed: Option<TypeId>, added: Option<TypeId>) {
+        self.signatures[table] = Self::signature_of(&mut self.component_ids, &self.tables[table]);
+        self.archetype_log.push(table);
+        Self::log_removed(&mut self.removed, &mut self.observers, removed, &self.row_entities[table]);
+        Self::log_added(&mut self.observers, added, &self.row_entities[table]);
+        self.flush_lifecycle();
+    }
+
+    fn log_removed(removed: &mut HashMap<TypeId, RemovedLog>, observers: &mut Observers, types: impl IntoIterator<Item = TypeId>, entities: &[Entity]) {
+        if entities.is_empty() {
+            return;
+        }
+        for type_id in types {
+            removed.entry(type_id).or_default().entities.extend_from_slice(entities);
+            observers.queue::<OnRemove>(type_id, entities);
+        }
+    }
+
+    fn log_added(observers: &mut Observers, types: impl IntoIterator<Item = TypeId>, entities: &[Entity]) {
+        for type_id in types {
+            observers.queue::<OnAdd>(type_id, entities);
+        }
+    }
+
+    fn signature_of(component_ids: &mut HashMap<TypeId, usize>, table: &Table) -> Signature {
+        let mut signature = Signature::default();
+        for metadata in table.type_metadata() {
+            let next_id = component_ids.len();
+            signature.insert(*component_ids.entry(metadata.id).or_insert(next_id));
+        }
+        signature
+    }
+}
+
+// Runs f on the world when dropped, so bookkeeping after a table operation also happens if a drop in it panics
+struct OnExit<'w, F: FnMut(&mut World)> {
+    world: &'w mut World,
+    f: F,
+}
+
+impl<F: FnMut(&mut World)> Drop for OnExit<'_, F> {
+    fn drop(&mut self) {
+        (self.f)(self.world)
+    }
+}
+
+// A table borrowed from a world. Only hands out changes that keep every row's entity and the table's signature
+// up to date, reading goes through Table
+pub struct TableMut<'w> {
+    world: &'w mut World,
+    idx: usize,
+}
+
+impl Deref for TableMut<'_> {
+    type Target = Table;
+
+    fn deref(&self) -> &Table {
+        &self.world.tables[self.idx]
+    }
+}
+
+impl TableMut<'_> {
+    pub fn push<B: DynamicBundle>(&mut self, data: B) -> Entity {
+        self.world.spawn_in(self.idx, data)
+    }
+
+    // Every new row becomes an entity
+    pub fn extend<I: IntoIterator<Item: DynamicBundle>>(&mut self, iter: I) {
+        let first = self.world.tables[self.idx].len();
+        self.world.tables[self.idx].extend(iter);
+        self.world.spawn_rows(self.idx, first);
+        self.world.flush_lifecycle();
+    }
+
+    // Replaces the row's data, the entity stays the same
+    pub fn insert_at<B: DynamicBundle>(&mut self, idx: usize, data: B) -> B {
+        self.world.tables[self.idx].insert_at(idx, data)
+    }
+
+    // Despawns the row's entity, the last row takes its place
+    pub fn swap_remove(&mut self, idx: usize) {
+        self.world.swap_remove_row(self.idx, idx, |table, row| table.swap_remove(row));
+    }
+
+    // Despawns the row's entity and hands its data back, the last row takes its place
+    pub fn swap_pop<B: DynamicBundle>(&mut self, idx: usize) -> B {
+        self.world.swap_remove_row(self.idx, idx, |table, row| table.swap_pop(row))
+    }
+
+    pub fn pop<B: DynamicBundle>(&mut self) -> B {
+        assert!(!self.is_empty());
+        self.swap_pop(self.len() - 1)
+    }
+
+    // Despawns the entities of count rows from idx, the rows after them keep their order
+    pub fn erase(&mut self, idx: usize, count: usize) {
+        self.world.erase_rows(self.idx, idx..idx + count, |table| table.erase(idx, count));
+    }
+
+    pub fn clear(&mut self) {
+        self.erase(0, self.len());
+    }
+
+    pub fn column_slices_mut<T: 'static>(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.world.tables[self.idx].column_slices_mut()
+    }
+
+    // Every row gets a T, queries see the new signature right away
+    pub fn add_column<T: 'static>(&mut self, f: impl FnMut(usize) -> T) {
+        self.world.tables[self.idx].add_column(f);
+        self.world.columns_changed(self.idx, None, Some(TypeId::of::<T>()));
+    }
+
+    // Every row loses its T, queries see the new signature right away
+    pub fn remove_column<T: 'static>(&mut self) -> Vec<T> {
+        let column = self.world.tables[self.idx].remove_column();
+        self.world.columns_changed(self.idx, Some(TypeId::of::<T>()), None);
+        column
+    }
+}
+
+// Which column types a table has, as a bitset of component ids
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signature {
+    bits: Vec<u64>,
+}
+
+impl Signature {
+    pub fn insert(&mut self, id: usize) {
+        let (word, bit) = (id / 64, id % 64);
+        if self.bits.len() <= word {
+            self.bits.resize(word + 1, 0);
+        }
+        self.bits[word] |= 1 << bit;
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.bits.get(id / 64).is_some_and(|word| word & (1 << (id % 64)) != 0)
+    }
+
+    pub fn contains_all(&self, other: &Signature) -> bool {
+        other.bits.iter().enumerate().all(|(idx, &word)| self.bits.get(idx).copied().unwrap_or(0) & word == word)
+    }
+}
+
+// The entities that lost a component of one type, oldest first
+#[derive(Default)]
+struct RemovedLog {
+    entities: Vec<Entity>,
+    // how many were logged before the last clear
+    cleared: usize,
+}
+
+// Reads the entities that lost a T, each reader only sees what was logged since its last read. Bound to the first
+// world it reads from
+pub struct RemovedComponents<T: 'static> {
+    world: Option<WorldId>,
+    // how many removals of T this reader has seen, cleared ones included
+    cursor: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> RemovedComponents<T> {
+    pub fn new() -> Self {
+        Self { world: None, cursor: 0, _marker: PhantomData }
+    }
+
+    // NB: despawned entities are still reported, but they're no longer alive
+    pub fn read<'w>(&mut self, world: &'w World) -> impl Iterator<Item = Entity> + 'w {
+        // the cursor counts removals in one world's log, it means nothing in another
+        let id = *self.world.get_or_insert(world.id());
+        assert_eq!(id, world.id(), "RemovedComponents used with a different world");
+        let unread = match world.removed.get(&TypeId::of::<T>()) {
+            Some(log) => {
+                let start = self.cursor.saturating_sub(log.cleared).min(log.entities.len());
+                self.cursor = log.cleared + log.entities.len();
+                &log.entities[start..]
+            }
+            None => &[],
+        };
+        unread.iter().copied()
+    }
+}
+
+impl<T: 'static> Default for RemovedComponents<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,182 @@
+use crate::entity::{Entity, Parent};
+use crate::world::World;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// What an observer was run for: the event, and the entity it's currently at if it was targeted
+pub struct Trigger<E> {
+    event: E,
+    target: Option<Entity>,
+    propagate: bool,
+}
+
+impl<E> Trigger<E> {
+    pub fn event(&self) -> &E {
+        &self.event
+    }
+
+    // observers run later see the changes
+    pub fn event_mut(&mut self) -> &mut E {
+        &mut self.event
+    }
+
+    pub fn target(&self) -> Option<Entity> {
+        self.target
+    }
+
+    // Whether a targeted trigger bubbles up to the target's Parent once this target's observers ran
+    pub fn propagate(&mut self, propagate: bool) {
+        self.propagate = propagate;
+    }
+}
+
+// Triggered for an entity right after it got a component, observed per component type
+pub struct OnAdd;
+
+// Triggered for an entity right after it lost a component, the same way RemovedComponents logs it
+pub struct OnRemove;
+
+// A component being added to or removed from an entity, waiting for its observers to run
+struct Lifecycle {
+    event: TypeId,
+    component: TypeId,
+    entity: Entity,
+}
+
+// An observer takes its Trigger<E> as Any, it's only ever run for triggers of its own E
+type Observer = Rc<RefCell<dyn FnMut(&mut World, &mut dyn Any)>>;
+
+// Every observer of a world, and the lifecycle events waiting for theirs to run
+#[derive(Default)]
+pub(super) struct Observers {
+    by_key: HashMap<ObserverKey, Vec<Observer>>,
+    // the keys of the observers of single entities, dropped once the entity is despawned
+    by_entity: HashMap<Entity, Vec<ObserverKey>>,
+    lifecycle: Vec<Lifecycle>,
+    // set while lifecycle observers run, what they add or remove is run by the same flush once they're done
+    flushing: bool,
+}
+
+impl Observers {
+    // Queues E for every entity, unless nothing observes E for the component
+    pub(super) fn queue<E: 'static>(&mut self, component: TypeId, entities: &[Entity]) {
+        if self.by_key.contains_key(&ObserverKey { component: Some(component), ..ObserverKey::of::<E>() }) {
+            self.lifecycle.extend(entities.iter().map(|&entity| Lifecycle { event: TypeId::of::<E>(), component, entity }));
+        }
+    }
+
+    pub(super) fn despawned(&mut self, entity: Entity) {
+        for key in self.by_entity.remove(&entity).unwrap_or_default() {
+            self.by_key.remove(&key);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct ObserverKey {
+    event: TypeId,
+    // set for OnAdd and OnRemove
+    component: Option<TypeId>,
+    // set for observers of a single entity
+    entity: Option<Entity>,
+}
+
+impl ObserverKey {
+    fn of<E: 'static>() -> Self {
+        Self { event: TypeId::of::<E>(), component: None, entity: None }
+    }
+}
+
+impl World {
+    // Runs f whenever an E is triggered, for every target it bubbles through
+    pub fn observe<E: 'static>(&mut self, f: impl FnMut(&mut World, &mut Trigger<E>) + 'static) {
+        self.add_observer(ObserverKey::of::<E>(), f);
+    }
+
+    // Runs f whenever an E is triggered for entity, or bubbles up to it. Dropped once the entity is despawned
+    pub fn observe_entity<E: 'static>(&mut self, entity: Entity, f: impl FnMut(&mut World, &mut Trigger<E>) + 'static) {
+        self.add_observer(ObserverKey { entity: Some(entity), ..ObserverKey::of::<E>() }, f);
+    }
+
+    pub fn on_add<T: 'static>(&mut self, f: impl FnMut(&mut World, &mut Trigger<OnAdd>) + 'static) {
+        self.add_observer(ObserverKey { component: Some(TypeId::of::<T>()), ..ObserverKey::of::<OnAdd>() }, f);
+    }
+
+    pub fn on_remove<T: 'static>(&mut self, f: impl FnMut(&mut World, &mut Trigger<OnRemove>) + 'static) {
+        self.add_observer(ObserverKey { component: Some(TypeId::of::<T>()), ..ObserverKey::of::<OnRemove>() }, f);
+    }
+
+    // Runs the observers of E right away, and hands the event back once they're done
+    pub fn trigger<E: 'static>(&mut self, event: E) -> E {
+        let mut trigger = Trigger { event, target: None, propagate: false };
+        self.run_observers(ObserverKey::of::<E>(), &mut trigger);
+        trigger.event
+    }
+
+    // Runs the observers of E for entity, then keeps going up through Parent while an observer asks it to propagate
+    pub fn trigger_for<E: 'static>(&mut self, entity: Entity, event: E) -> E {
+        let mut trigger = Trigger { event, target: Some(entity), propagate: false };
+        // NB: bounded, so a cycle of parents can't bubble forever
+        for _ in 0..=self.entities().len() {
+            let Some(target) = trigger.target.filter(|&target| self.entities().contains(target)) else {
+                break;
+            };
+            trigger.propagate = false;
+            self.run_observers(ObserverKey { entity: Some(target), ..ObserverKey::of::<E>() }, &mut trigger);
+            self.run_observers(ObserverKey::of::<E>(), &mut trigger);
+            if !trigger.propagate {
+                break;
+            }
+            trigger.target = self.query::<(&Parent,)>().ok().and_then(|query| query.get(target).ok().map(|(parent,)| parent.0));
+        }
+        trigger.event
+    }
+
+    fn add_observer<E: 'static>(&mut self, key: ObserverKey, mut f: impl FnMut(&mut World, &mut Trigger<E>) + 'static) {
+        let observer: Observer = Rc::new(RefCell::new(move |world: &mut World, trigger: &mut dyn Any| {
+            f(world, trigger.downcast_mut().expect("Observers only run for their own event"))
+        }));
+        if let Some(entity) = key.entity
+            && !self.observers.by_key.contains_key(&key)
+        {
+            self.observers.by_entity.entry(entity).or_default().push(key);
+        }
+        self.observers.by_key.entry(key).or_default().push(observer);
+    }
+
+    fn run_observers<E: 'static>(&mut self, key: ObserverKey, trigger: &mut Trigger<E>) {
+        let Some(observers) = self.observers.by_key.get(&key).cloned() else {
+            return;
+        };
+        for observer in observers {
+            // NB: an observer can't run inside itself, one that triggers its own event again is skipped in release
+            let observer = observer.try_borrow_mut();
+            debug_assert!(observer.is_ok(), "An observer triggered its own event");
+            if let Ok(mut observer) = observer {
+                observer(self, trigger);
+            }
+        }
+    }
+
+    // Runs the observers of every component added or removed so far, including the ones they add or remove
+    pub(super) fn flush_lifecycle(&mut self) {
+        // the outer flush picks up what observers queue
+        if self.observers.flushing {
+            return;
+        }
+        self.observers.flushing = true;
+        while !self.observers.lifecycle.is_empty() {
+            for Lifecycle { event, component, entity } in std::mem::take(&mut self.observers.lifecycle) {
+                let key = ObserverKey { event, component: Some(component), entity: None };
+                if event == TypeId::of::<OnAdd>() {
+                    self.run_observers(key, &mut Trigger { event: OnAdd, target: Some(entity), propagate: false });
+                } else {
+                    self.run_observers(key, &mut Trigger { event: OnRemove, target: Some(entity), propagate: false });
+                }
+            }
+        }
+        self.observers.flushing = false;
+    }
+}
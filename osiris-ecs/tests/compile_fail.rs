@@ -0,0 +1,5 @@
+// Bundles with the same type twice are rejected at compile time, see tests/ui
+#[test]
+fn duplicate_bundle_types_fail_to_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,19 @@
+use osiris_ecs::storage::{Bundle, DynamicBundle, TypeMetadata};
+
+#[derive(Bundle)]
+struct Position {
+    x: f32,
+}
+
+#[derive(Bundle)]
+struct Body {
+    mass: f32,
+    #[bundle]
+    position: Position,
+}
+
+const METADATA: &[TypeMetadata] = Body::METADATA;
+
+fn main() {
+    println!("{}", METADATA.len());
+}
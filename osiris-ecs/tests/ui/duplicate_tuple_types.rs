@@ -0,0 +1,7 @@
+use osiris_ecs::storage::{DynamicBundle, TypeMetadata};
+
+const METADATA: &[TypeMetadata] = <(u32, f32, u32) as DynamicBundle>::METADATA;
+
+fn main() {
+    println!("{}", METADATA.len());
+}